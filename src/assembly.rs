@@ -0,0 +1,798 @@
+//! A text format describing a GIF block by block, meant for writing test fixtures by hand.
+//!
+//! Each line is a directive followed by `key=value` arguments, or a data line adding to the block
+//! of the directive above it. `;` starts a comment. For example:
+//!
+//! ```text
+//! version 89a
+//! screen logical_screen_width=2 logical_screen_height=2 background_color_index=0
+//! colors #000000 #ffffff
+//! comment
+//! sub_block "made by hand"
+//! image image_width=2 image_height=2 lzw_minimum_code_size=2
+//! indices 0 1
+//! indices 1 0
+//! ```
+//!
+//! The directives are, in data stream order:
+//!
+//! - `version 87a` or `version 89a`, first.
+//! - `screen`, the logical screen descriptor. Its `colors` lines make the global color table.
+//! - `graphic_control`, attached to the image or plain text extension right after it if any.
+//! - `image`, with `colors` lines making the local color table, and either `sub_block` lines
+//!   holding the compressed image data or `indices` lines holding the rows of color indices to
+//!   compress, from top to bottom even if the image is interlaced.
+//! - `plain_text`, `comment`, `application` and `unknown_extension`, with `sub_block` lines.
+//!
+//! Arguments are named after the fields they set and default to 0, except for the dimensions of
+//! the logical screen and of images and the LZW minimum code size, which are required. Numbers
+//! are decimal or hexadecimal (`0x2c`). A `sub_block` line holds hex bytes (`4e4554`) or quoted
+//! strings (`"NET"`, with `\"`, `\\`, `\n` and `\x2c` escapes), one sub-block per line.
+//!
+//! [`disassemble`] writes every bit of the packed fields and keeps sub-blocks as they are, so
+//! [`assemble`] gives back the exact bytes of any data stream [`decoder::decode`] accepts, up to
+//! the trailer.
+//!
+//! [`decoder::decode`]: crate::decoder::decode
+
+use crate::{
+    interlace, ApplicationExtension, Block, ColorTable, CommentExtension, DataSubBlock,
+    DataSubBlocks, GifData, GraphicControlExtension, GraphicRenderingBlock, ImageDescriptor,
+    LogicalScreenDescriptor, PlainTextExtension, TableBasedImageData, Version,
+};
+use std::{borrow::Cow, error, fmt, fmt::Write};
+
+/// Colors per `colors` line in a disassembly.
+const COLORS_PER_LINE: usize = 8;
+
+/// The directives making a block out of their arguments and data lines.
+const DIRECTIVES: &[&str] = &[
+    "screen",
+    "graphic_control",
+    "image",
+    "plain_text",
+    "comment",
+    "application",
+    "unknown_extension",
+];
+
+/// A line of the text that isn't valid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+    /// Numbered from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for AssembleError {}
+
+/// Describes `gif` in the text format of this module.
+pub fn disassemble(gif: &GifData) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "version {}",
+        match gif.version {
+            Version::V87a => "87a",
+            Version::V89a => "89a",
+        }
+    );
+
+    let lsd = &gif.logical_screen_descriptor;
+    let _ = write!(
+        out,
+        "screen logical_screen_width={} logical_screen_height={} color_resolution={} sort_flag={}",
+        lsd.logical_screen_width,
+        lsd.logical_screen_height,
+        lsd.color_resolution(),
+        lsd.sort_flag()
+    );
+    if lsd.global_color_table.is_none() && lsd.global_color_table_size() != 0 {
+        let _ = write!(
+            out,
+            " global_color_table_size={}",
+            lsd.global_color_table_size()
+        );
+    }
+    let _ = writeln!(
+        out,
+        " background_color_index={} pixel_aspect_ratio={}",
+        lsd.background_color_index, lsd.pixel_aspect_ratio
+    );
+    if let Some(table) = &lsd.global_color_table {
+        write_colors(&mut out, table);
+    }
+
+    for block in &gif.blocks {
+        match block {
+            Block::Application(ext) => {
+                let _ = writeln!(
+                    out,
+                    "application identifier={} authentication_code={}",
+                    quote(&ext.identifier),
+                    quote(&ext.authentication_code)
+                );
+                write_sub_blocks(&mut out, &ext.data, true);
+            }
+            Block::Comment(ext) => {
+                out.push_str("comment\n");
+                write_sub_blocks(&mut out, &ext.data, true);
+            }
+            Block::GraphicControl(ext) => write_graphic_control_extension(&mut out, ext),
+            Block::Unknown { label, data, .. } => {
+                let _ = writeln!(out, "unknown_extension label=0x{:02x}", label);
+                write_sub_blocks(&mut out, data, true);
+            }
+            Block::GraphicRendering(GraphicRenderingBlock::PlainText(ext)) => {
+                if let Some(gce) = &ext.graphic_control_extension {
+                    write_graphic_control_extension(&mut out, gce);
+                }
+                let _ = writeln!(
+                    out,
+                    "plain_text text_grid_left_position={} text_grid_top_position={} \
+                     text_grid_width={} text_grid_height={} character_cell_width={} \
+                     character_cell_height={} text_foreground_color_index={} \
+                     text_background_color_index={}",
+                    ext.text_grid_left_position,
+                    ext.text_grid_top_position,
+                    ext.text_grid_width,
+                    ext.text_grid_height,
+                    ext.character_cell_width,
+                    ext.character_cell_height,
+                    ext.text_foreground_color_index,
+                    ext.text_background_color_index
+                );
+                write_sub_blocks(&mut out, &ext.data, true);
+            }
+            Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+                if let Some(gce) = &image.graphic_control_extension {
+                    write_graphic_control_extension(&mut out, gce);
+                }
+                let _ = write!(
+                    out,
+                    "image image_left_position={} image_top_position={} image_width={} \
+                     image_height={} interlace_flag={} sort_flag={}",
+                    image.image_left_position,
+                    image.image_top_position,
+                    image.image_width,
+                    image.image_height,
+                    image.interlace_flag(),
+                    image.sort_flag()
+                );
+                if image.local_color_table.is_none() && image.local_color_table_size() != 0 {
+                    let _ = write!(
+                        out,
+                        " local_color_table_size={}",
+                        image.local_color_table_size()
+                    );
+                }
+                let reserved = (image.packed_fields >> 3) & 0b11;
+                if reserved != 0 {
+                    let _ = write!(out, " reserved={}", reserved);
+                }
+                let _ = writeln!(
+                    out,
+                    " lzw_minimum_code_size={}",
+                    image.image_data.lzw_minimum_code_size
+                );
+                if let Some(table) = &image.local_color_table {
+                    write_colors(&mut out, table);
+                }
+                write_sub_blocks(&mut out, &image.image_data.image_data, false);
+            }
+        }
+    }
+    out
+}
+
+fn write_graphic_control_extension(out: &mut String, ext: &GraphicControlExtension) {
+    let _ = write!(
+        out,
+        "graphic_control disposal_method={} user_input_flag={} transparent_color_flag={} \
+         delay_time={} transparent_color_index={}",
+        u8::from(ext.disposal_method()),
+        ext.user_input_flag(),
+        ext.transparent_color_flag(),
+        ext.delay_time,
+        ext.transparent_color_index
+    );
+    let reserved = ext.packed_fields >> 5;
+    if reserved != 0 {
+        let _ = write!(out, " reserved={}", reserved);
+    }
+    out.push('\n');
+}
+
+fn write_colors(out: &mut String, table: &ColorTable) {
+    for colors in table.pixels().chunks(3 * COLORS_PER_LINE) {
+        out.push_str("colors");
+        for color in colors.chunks(3) {
+            let _ = write!(out, " #{:02x}{:02x}{:02x}", color[0], color[1], color[2]);
+        }
+        out.push('\n');
+    }
+}
+
+/// Writes a `sub_block` line per sub-block, as a string if `text` is set and every byte is
+/// printable ASCII, in hex otherwise.
+fn write_sub_blocks(out: &mut String, data: &DataSubBlocks, text: bool) {
+    for block in &data.blocks {
+        out.push_str("sub_block ");
+        if text && block.data.iter().all(|&b| (0x20..0x7f).contains(&b)) {
+            out.push_str(&quote(&block.data));
+        } else {
+            for b in block.data.iter() {
+                let _ = write!(out, "{:02x}", b);
+            }
+        }
+        out.push('\n');
+    }
+}
+
+fn quote(bytes: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &b in bytes {
+        match b {
+            b'"' => quoted.push_str("\\\""),
+            b'\\' => quoted.push_str("\\\\"),
+            0x20..=0x7e => quoted.push(b as char),
+            b => {
+                let _ = write!(quoted, "\\x{:02x}", b);
+            }
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Turns a text in the format of this module back into a GIF.
+pub fn assemble(text: &str) -> Result<Vec<u8>, AssembleError> {
+    let mut assembler = Assembler {
+        version: None,
+        screen: None,
+        blocks: Vec::new(),
+        graphic_control_extension: None,
+        pending: None,
+    };
+    for (i, text) in text.lines().enumerate() {
+        let line = i + 1;
+        let tokens = tokenize(text).map_err(|message| AssembleError { line, message })?;
+        if tokens
+            .first()
+            .is_some_and(|keyword| DIRECTIVES.contains(keyword))
+        {
+            assembler.finish_pending()?;
+        }
+        assembler
+            .line(line, &tokens)
+            .map_err(|message| AssembleError { line, message })?;
+    }
+    let line = text.lines().count().max(1);
+    assembler.finish_pending()?;
+    // Followed by the trailer, it applies to nothing.
+    if let Some(gce) = assembler.graphic_control_extension.take() {
+        assembler.blocks.push(Block::GraphicControl(gce));
+    }
+    let gif = match (assembler.version, assembler.screen) {
+        (Some(version), Some(logical_screen_descriptor)) => GifData {
+            version,
+            logical_screen_descriptor,
+            blocks: assembler.blocks,
+            trailer: None,
+        },
+        (None, _) => return Err(syntax(line, "missing 'version'")),
+        (Some(_), None) => return Err(syntax(line, "missing 'screen'")),
+    };
+    // The encoder shouldn't reject what the checks above let through, but if it does there's no
+    // telling which line caused it.
+    gif.encode(&gif.version, false).map_err(|e| AssembleError {
+        line,
+        message: e.to_string(),
+    })
+}
+
+fn syntax(line: usize, message: &str) -> AssembleError {
+    AssembleError {
+        line,
+        message: message.to_string(),
+    }
+}
+
+struct Assembler {
+    version: Option<Version>,
+    screen: Option<LogicalScreenDescriptor<'static>>,
+    blocks: Vec<Block<'static>>,
+    /// Waiting to be attached to the next graphic rendering block.
+    graphic_control_extension: Option<GraphicControlExtension>,
+    /// The last directive, waiting for its data lines.
+    pending: Option<Pending>,
+}
+
+struct Pending {
+    line: usize,
+    directive: String,
+    args: Args,
+    colors: Option<Vec<u8>>,
+    sub_blocks: Vec<DataSubBlock<'static>>,
+    indices: Option<Vec<u8>>,
+}
+
+/// The `key=value` arguments of a directive, taken out as they're used.
+struct Args(Vec<(String, Value)>);
+
+enum Value {
+    Number(u64),
+    Bytes(Vec<u8>),
+}
+
+impl Args {
+    fn take(&mut self, key: &str) -> Option<Value> {
+        let i = self.0.iter().position(|(k, _)| k == key)?;
+        Some(self.0.remove(i).1)
+    }
+
+    fn number(&mut self, key: &str, max: u64) -> Result<Option<u64>, String> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Value::Number(n)) if n <= max => Ok(Some(n)),
+            Some(Value::Number(n)) => Err(format!("{}={} is greater than {}", key, n, max)),
+            Some(Value::Bytes(_)) => Err(format!("{} expects a number", key)),
+        }
+    }
+
+    fn u8(&mut self, key: &str, max: u8) -> Result<u8, String> {
+        Ok(self.number(key, max as u64)?.unwrap_or(0) as u8)
+    }
+
+    fn u16(&mut self, key: &str) -> Result<u16, String> {
+        Ok(self.number(key, u16::MAX as u64)?.unwrap_or(0) as u16)
+    }
+
+    fn required(&mut self, key: &str, max: u64) -> Result<u64, String> {
+        self.number(key, max)?
+            .ok_or_else(|| format!("missing {}", key))
+    }
+
+    fn bytes(&mut self, key: &str, len: usize) -> Result<Vec<u8>, String> {
+        match self.take(key) {
+            Some(Value::Bytes(bytes)) if bytes.len() == len => Ok(bytes),
+            Some(Value::Bytes(bytes)) => Err(format!(
+                "{} must be {} bytes long, not {}",
+                key,
+                len,
+                bytes.len()
+            )),
+            Some(Value::Number(_)) => Err(format!("{} expects a string", key)),
+            None => Err(format!("missing {}", key)),
+        }
+    }
+
+    /// Fails if any argument wasn't used.
+    fn finish(self) -> Result<(), String> {
+        match self.0.first() {
+            Some((key, _)) => Err(format!("unknown argument '{}'", key)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Assembler {
+    fn line(&mut self, line: usize, tokens: &[&str]) -> Result<(), String> {
+        let (&keyword, tokens) = match tokens.split_first() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+        match keyword {
+            "colors" => {
+                let colors = self.data(keyword, &["screen", "image"])?;
+                let colors = colors.colors.get_or_insert_with(Vec::new);
+                for token in tokens {
+                    colors.extend(parse_color(token)?);
+                }
+            }
+            "sub_block" => {
+                let pending = self.data(
+                    keyword,
+                    &[
+                        "image",
+                        "plain_text",
+                        "comment",
+                        "application",
+                        "unknown_extension",
+                    ],
+                )?;
+                if pending.indices.is_some() {
+                    return Err("'sub_block' and 'indices' can't be mixed".to_string());
+                }
+                let mut data = Vec::new();
+                for token in tokens {
+                    data.extend(parse_bytes(token)?);
+                }
+                if data.is_empty() || data.len() > DataSubBlock::MAX_SIZE {
+                    return Err(format!(
+                        "a sub-block holds 1 to {} bytes, not {}",
+                        DataSubBlock::MAX_SIZE,
+                        data.len()
+                    ));
+                }
+                pending.sub_blocks.push(DataSubBlock {
+                    block_size: data.len() as u8,
+                    data: Cow::Owned(data),
+                    span: None,
+                });
+            }
+            "indices" => {
+                let pending = self.data(keyword, &["image"])?;
+                if !pending.sub_blocks.is_empty() {
+                    return Err("'sub_block' and 'indices' can't be mixed".to_string());
+                }
+                let indices = pending.indices.get_or_insert_with(Vec::new);
+                for token in tokens {
+                    match parse_number(token) {
+                        Some(index) if index <= u8::MAX as u64 => indices.push(index as u8),
+                        _ => return Err(format!("invalid color index '{}'", token)),
+                    }
+                }
+            }
+            "version" => {
+                if self.version.is_some() {
+                    return Err("'version' can only appear once".to_string());
+                }
+                self.version = Some(match tokens {
+                    ["87a"] => Version::V87a,
+                    ["89a"] => Version::V89a,
+                    _ => return Err("'version' expects 87a or 89a".to_string()),
+                });
+            }
+            keyword if DIRECTIVES.contains(&keyword) => {
+                match (keyword, &self.version, &self.screen) {
+                    (_, None, _) => return Err("expected 'version' first".to_string()),
+                    ("screen", _, Some(_)) => {
+                        return Err("'screen' can only appear once".to_string())
+                    }
+                    ("screen", _, None) => {}
+                    (_, _, None) => return Err("expected 'screen' first".to_string()),
+                    _ => {}
+                }
+                let mut args = Vec::new();
+                for token in tokens {
+                    let (key, value) = token
+                        .split_once('=')
+                        .ok_or_else(|| format!("expected key=value, got '{}'", token))?;
+                    let value = if value.starts_with('"') {
+                        Value::Bytes(parse_bytes(value)?)
+                    } else {
+                        Value::Number(
+                            parse_number(value)
+                                .ok_or_else(|| format!("invalid number '{}'", value))?,
+                        )
+                    };
+                    args.push((key.to_string(), value));
+                }
+                self.pending = Some(Pending {
+                    line,
+                    directive: keyword.to_string(),
+                    args: Args(args),
+                    colors: None,
+                    sub_blocks: Vec::new(),
+                    indices: None,
+                });
+            }
+            keyword => return Err(format!("unknown directive '{}'", keyword)),
+        }
+        Ok(())
+    }
+
+    /// The pending directive, if it's one of `directives` taking `keyword` lines.
+    fn data(&mut self, keyword: &str, directives: &[&str]) -> Result<&mut Pending, String> {
+        let pending = self
+            .pending
+            .as_mut()
+            .ok_or_else(|| format!("'{}' must follow a directive", keyword))?;
+        if !directives.contains(&pending.directive.as_str()) {
+            return Err(format!(
+                "'{}' can't follow '{}'",
+                keyword, pending.directive
+            ));
+        }
+        Ok(pending)
+    }
+
+    /// Turns the pending directive into a block, failing with the line of the directive.
+    fn finish_pending(&mut self) -> Result<(), AssembleError> {
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let line = pending.line;
+        self.finish(pending)
+            .map_err(|message| AssembleError { line, message })
+    }
+
+    fn finish(&mut self, mut pending: Pending) -> Result<(), String> {
+        let args = &mut pending.args;
+        let color_table = match pending.colors.take() {
+            Some(colors) => Some(ColorTable::new(colors).ok_or_else(|| {
+                "a color table holds a power of 2 between 2 and 256 colors".to_string()
+            })?),
+            None => None,
+        };
+        let data = DataSubBlocks {
+            blocks: std::mem::take(&mut pending.sub_blocks),
+        };
+
+        let block = match pending.directive.as_str() {
+            "screen" => {
+                let mut lsd = LogicalScreenDescriptor::new(
+                    args.required("logical_screen_width", u16::MAX as u64)? as u16,
+                    args.required("logical_screen_height", u16::MAX as u64)? as u16,
+                );
+                lsd.packed_fields = packed_fields_with_color_table(
+                    color_table.as_ref(),
+                    args.number("global_color_table_size", 7)?,
+                    "global_color_table_size",
+                )?;
+                crate::set_bits(
+                    &mut lsd.packed_fields,
+                    4,
+                    3,
+                    args.u8("color_resolution", 7)?,
+                );
+                crate::set_bits(&mut lsd.packed_fields, 3, 1, args.u8("sort_flag", 1)?);
+                lsd.background_color_index = args.u8("background_color_index", u8::MAX)?;
+                lsd.pixel_aspect_ratio = args.u8("pixel_aspect_ratio", u8::MAX)?;
+                lsd.global_color_table = color_table;
+                self.screen = Some(lsd);
+                None
+            }
+            "graphic_control" => {
+                let mut packed_fields = 0;
+                crate::set_bits(&mut packed_fields, 5, 3, args.u8("reserved", 7)?);
+                crate::set_bits(&mut packed_fields, 2, 3, args.u8("disposal_method", 7)?);
+                crate::set_bits(&mut packed_fields, 1, 1, args.u8("user_input_flag", 1)?);
+                crate::set_bits(
+                    &mut packed_fields,
+                    0,
+                    1,
+                    args.u8("transparent_color_flag", 1)?,
+                );
+                let gce = GraphicControlExtension {
+                    packed_fields,
+                    delay_time: args.u16("delay_time")?,
+                    transparent_color_index: args.u8("transparent_color_index", u8::MAX)?,
+                    span: None,
+                };
+                // Two in a row, the first one applies to nothing.
+                if let Some(previous) = self.graphic_control_extension.replace(gce) {
+                    self.blocks.push(Block::GraphicControl(previous));
+                }
+                None
+            }
+            "image" => {
+                let image_width = args.required("image_width", u16::MAX as u64)? as u16;
+                let image_height = args.required("image_height", u16::MAX as u64)? as u16;
+                let lzw_minimum_code_size =
+                    args.required("lzw_minimum_code_size", u8::MAX as u64)? as u8;
+                let mut packed_fields = packed_fields_with_color_table(
+                    color_table.as_ref(),
+                    args.number("local_color_table_size", 7)?,
+                    "local_color_table_size",
+                )?;
+                let interlace_flag = args.u8("interlace_flag", 1)?;
+                crate::set_bits(&mut packed_fields, 6, 1, interlace_flag);
+                crate::set_bits(&mut packed_fields, 5, 1, args.u8("sort_flag", 1)?);
+                crate::set_bits(&mut packed_fields, 3, 2, args.u8("reserved", 3)?);
+                let image_data = match pending.indices.take() {
+                    Some(mut indices) => {
+                        // Only compressing needs a valid one, sub-blocks are written as they are.
+                        if !(2..=8).contains(&lzw_minimum_code_size) {
+                            return Err(format!(
+                                "lzw_minimum_code_size={} can't compress 'indices', it must be \
+                                 between 2 and 8",
+                                lzw_minimum_code_size
+                            ));
+                        }
+                        let len = image_width as usize * image_height as usize;
+                        if indices.len() != len {
+                            return Err(format!(
+                                "{} color indices for a {}x{} image",
+                                indices.len(),
+                                image_width,
+                                image_height
+                            ));
+                        }
+                        if interlace_flag == 1 {
+                            indices = interlace::interlace(&indices, image_width as usize);
+                        }
+                        TableBasedImageData::compress(&indices, lzw_minimum_code_size)
+                            .map_err(|e| e.to_string())?
+                    }
+                    None => TableBasedImageData {
+                        lzw_minimum_code_size,
+                        image_data: data,
+                        span: None,
+                    },
+                };
+                Some(Block::GraphicRendering(GraphicRenderingBlock::Image(
+                    ImageDescriptor {
+                        image_left_position: args.u16("image_left_position")?,
+                        image_top_position: args.u16("image_top_position")?,
+                        image_width,
+                        image_height,
+                        packed_fields,
+                        local_color_table: color_table,
+                        image_data,
+                        graphic_control_extension: self.graphic_control_extension.take(),
+                        span: None,
+                    },
+                )))
+            }
+            "plain_text" => Some(Block::GraphicRendering(GraphicRenderingBlock::PlainText(
+                PlainTextExtension {
+                    text_grid_left_position: args.u16("text_grid_left_position")?,
+                    text_grid_top_position: args.u16("text_grid_top_position")?,
+                    text_grid_width: args.u16("text_grid_width")?,
+                    text_grid_height: args.u16("text_grid_height")?,
+                    character_cell_width: args.u8("character_cell_width", u8::MAX)?,
+                    character_cell_height: args.u8("character_cell_height", u8::MAX)?,
+                    text_foreground_color_index: args.u8("text_foreground_color_index", u8::MAX)?,
+                    text_background_color_index: args.u8("text_background_color_index", u8::MAX)?,
+                    data,
+                    graphic_control_extension: self.graphic_control_extension.take(),
+                    span: None,
+                },
+            ))),
+            "comment" => Some(Block::Comment(CommentExtension { data, span: None })),
+            "application" => Some(Block::Application(ApplicationExtension {
+                identifier: Cow::Owned(args.bytes("identifier", 8)?),
+                authentication_code: Cow::Owned(args.bytes("authentication_code", 3)?),
+                data,
+                span: None,
+            })),
+            _ => Some(Block::Unknown {
+                label: args.u8("label", u8::MAX)?,
+                data,
+                span: None,
+            }),
+        };
+        pending.args.finish()?;
+
+        if let Some(block) = block {
+            if !matches!(block, Block::GraphicRendering(_)) {
+                if let Some(gce) = self.graphic_control_extension.take() {
+                    self.blocks.push(Block::GraphicControl(gce));
+                }
+            }
+            self.blocks.push(block);
+        }
+        Ok(())
+    }
+}
+
+/// The color table flag and size bits of packed fields. The size comes from the color table if
+/// there's one, from the `size` argument otherwise.
+fn packed_fields_with_color_table(
+    color_table: Option<&ColorTable>,
+    size: Option<u64>,
+    key: &str,
+) -> Result<u8, String> {
+    match (color_table, size) {
+        (Some(table), Some(size)) if size != table.size_bits() as u64 => Err(format!(
+            "{}={} doesn't match a color table of {} colors",
+            key,
+            size,
+            table.len()
+        )),
+        (Some(table), _) => Ok(0x80 | table.size_bits()),
+        (None, size) => Ok(size.unwrap_or(0) as u8),
+    }
+}
+
+/// Splits a line in whitespace separated tokens up to the comment, if any. Quoted strings are
+/// kept whole, quotes included.
+fn tokenize(line: &str) -> Result<Vec<&str>, String> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut end = line.len();
+    let (mut quoted, mut escaped) = (false, false);
+    for (i, c) in line.char_indices() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            ';' => {
+                end = i;
+                break;
+            }
+            c if c.is_whitespace() => {
+                if let Some(start) = start.take() {
+                    tokens.push(&line[start..i]);
+                }
+            }
+            c => {
+                start.get_or_insert(i);
+                quoted = c == '"';
+            }
+        }
+    }
+    if quoted {
+        return Err("unterminated string".to_string());
+    }
+    if let Some(start) = start {
+        tokens.push(&line[start..end]);
+    }
+    Ok(tokens)
+}
+
+fn parse_number(token: &str) -> Option<u64> {
+    match token.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+fn parse_color(token: &str) -> Result<[u8; 3], String> {
+    let invalid = || format!("invalid color '{}', expected #rrggbb", token);
+    let hex = token.strip_prefix('#').ok_or_else(invalid)?;
+    match parse_hex(hex).as_deref() {
+        Some(&[r, g, b]) => Ok([r, g, b]),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Hex bytes or a quoted string.
+fn parse_bytes(token: &str) -> Result<Vec<u8>, String> {
+    let string = match token
+        .strip_prefix('"')
+        .and_then(|token| token.strip_suffix('"'))
+    {
+        Some(string) => string,
+        None => return parse_hex(token).ok_or_else(|| format!("invalid hex bytes '{}'", token)),
+    };
+    let mut bytes = Vec::new();
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('"') => bytes.push(b'"'),
+            Some('\\') => bytes.push(b'\\'),
+            Some('n') => bytes.push(b'\n'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match parse_hex(&hex).as_deref() {
+                    Some(&[b]) => bytes.push(b),
+                    _ => return Err(format!("invalid escape '\\x{}'", hex)),
+                }
+            }
+            Some(c) => return Err(format!("invalid escape '\\{}'", c)),
+            None => return Err(format!("invalid string {}", token)),
+        }
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,328 @@
+//! Conversion between GIF87a and GIF89a, mitigating and reporting what GIF87a can't hold.
+
+use crate::{
+    Block, ColorTable, DisposalMethod, GifData, GifError, GraphicControlExtension,
+    GraphicRenderingBlock, ImageDescriptor, TableBasedImageData, Version,
+};
+use log::info;
+use std::{collections::HashMap, fmt, mem};
+
+/// The version to convert to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    V87a,
+    V89a,
+    /// The oldest version able to hold every block, see [`GifData::minimal_version`].
+    Auto,
+}
+
+/// Something a conversion to GIF87a lost or altered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Loss {
+    /// The animation was flattened to its first frame, dropping `frames` images.
+    Animation {
+        frames: usize,
+    },
+    /// Transparent pixels were replaced by the background color.
+    Transparency,
+    /// The loop count of a NETSCAPE2.0 or ANIMEXTS1.0 extension, 0 meaning forever.
+    LoopCount(u16),
+    /// An application extension, identified by its identifier and authentication code.
+    ApplicationExtension(Vec<u8>),
+    Comments {
+        count: usize,
+    },
+    PlainText {
+        count: usize,
+    },
+    /// Graphic control extensions with a delay, a disposal method, the user input flag or
+    /// transparency were dropped, along with what they asked of the viewer.
+    GraphicControl {
+        count: usize,
+    },
+}
+
+impl fmt::Display for Loss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Animation { frames } => write!(
+                f,
+                "the animation was flattened to its first frame, dropping {} image(s)",
+                frames
+            ),
+            Self::Transparency => f.write_str("transparency was replaced by the background color"),
+            Self::LoopCount(0) => f.write_str("the loop count (forever) was dropped"),
+            Self::LoopCount(loop_count) => {
+                write!(f, "the loop count ({}) was dropped", loop_count)
+            }
+            Self::ApplicationExtension(identifier) => write!(
+                f,
+                "the {} application extension was dropped",
+                String::from_utf8_lossy(identifier)
+            ),
+            Self::Comments { count } => write!(f, "{} comment extension(s) were dropped", count),
+            Self::PlainText { count } => {
+                write!(f, "{} plain text extension(s) were dropped", count)
+            }
+            Self::GraphicControl { count } => write!(
+                f,
+                "{} graphic control extension(s) with a delay, disposal method, user input flag or \
+                 transparency were dropped",
+                count
+            ),
+        }
+    }
+}
+
+/// The result of [`GifData::convert`].
+#[derive(Debug, Clone)]
+pub struct Conversion<'a> {
+    pub gif: GifData<'a>,
+    /// Empty unless converting to GIF87a a data stream that needs GIF89a.
+    pub losses: Vec<Loss>,
+}
+
+impl<'a> GifData<'a> {
+    /// GIF89a if there's any extension that GIF87a doesn't define, GIF87a otherwise. Unknown
+    /// extensions don't count, as GIF87a already allows extensions it doesn't define.
+    pub fn minimal_version(&self) -> Version {
+        let v89a = self.blocks.iter().any(|block| match block {
+            Block::Application(_)
+            | Block::Comment(_)
+            | Block::GraphicControl(_)
+            | Block::GraphicRendering(GraphicRenderingBlock::PlainText(_)) => true,
+            Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+                image.graphic_control_extension.is_some()
+            }
+            Block::Unknown { .. } => false,
+        });
+        if v89a {
+            Version::V89a
+        } else {
+            Version::V87a
+        }
+    }
+
+    /// Converts to `target`, which only changes the version unless converting to GIF87a a data
+    /// stream that needs GIF89a. Then every extension GIF87a doesn't define is dropped, and an
+    /// animated or transparent GIF is replaced by its first frame as a viewer displays it, with
+    /// transparent pixels in the background color. Everything lost is reported. Converting to
+    /// GIF87a also clears the bits it reserves, like the sort flags.
+    pub fn convert(&self, target: Target) -> Result<Conversion<'a>, GifError> {
+        let version = match target {
+            Target::V87a => Version::V87a,
+            Target::V89a => Version::V89a,
+            Target::Auto => self.minimal_version(),
+        };
+        let mut gif = GifData {
+            version,
+            ..self.clone()
+        };
+        if version == Version::V87a {
+            clear_reserved_bits(&mut gif);
+        }
+        if version == Version::V89a || self.minimal_version() == Version::V87a {
+            return Ok(Conversion {
+                gif,
+                losses: Vec::new(),
+            });
+        }
+
+        let mut losses = Vec::new();
+        if let Some(loop_count) = self.loop_count() {
+            losses.push(Loss::LoopCount(loop_count));
+        }
+        for ext in self.application_extensions() {
+            if ext.netscape().is_none() {
+                let identifier = [&*ext.identifier, &*ext.authentication_code].concat();
+                losses.push(Loss::ApplicationExtension(identifier));
+            }
+        }
+        let comments = self.comment_extensions().count();
+        if comments > 0 {
+            losses.push(Loss::Comments { count: comments });
+        }
+        let plain_texts = self
+            .graphic_rendering_blocks()
+            .filter(|block| matches!(block, GraphicRenderingBlock::PlainText(_)))
+            .count();
+        if plain_texts > 0 {
+            losses.push(Loss::PlainText { count: plain_texts });
+        }
+
+        // GIF87a displays every image at once, which is only what GIF89a does without graphic
+        // control extensions. Those kept as blocks of their own apply to the next image too.
+        let images = self.images().count();
+        let graphic_control_extensions: Vec<_> = self
+            .blocks
+            .iter()
+            .filter_map(|block| match block {
+                Block::GraphicControl(ext) => Some(ext),
+                Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+                    image.graphic_control_extension.as_ref()
+                }
+                _ => None,
+            })
+            .collect();
+        let animated = images > 1 && !graphic_control_extensions.is_empty();
+        let transparent = images > 0
+            && graphic_control_extensions
+                .iter()
+                .any(|ext| ext.transparent_color_flag() == 1);
+        let flattened = if animated || transparent {
+            match self.frames().next() {
+                Some(frame) => Some(flatten(&mut gif, &frame?.pixels)?),
+                None => None,
+            }
+        } else {
+            None
+        };
+        if animated {
+            losses.push(Loss::Animation { frames: images - 1 });
+        }
+        if transparent {
+            losses.push(Loss::Transparency);
+        }
+        let graphic_controls = graphic_control_extensions
+            .iter()
+            .filter(|ext| !is_default(ext))
+            .count();
+        if graphic_controls > 0 {
+            losses.push(Loss::GraphicControl {
+                count: graphic_controls,
+            });
+        }
+
+        let mut flattened =
+            flattened.map(|image| Block::GraphicRendering(GraphicRenderingBlock::Image(image)));
+        // From the blocks of `gif`, whose reserved bits are already cleared.
+        gif.blocks = mem::take(&mut gif.blocks)
+            .into_iter()
+            .filter_map(|block| match block {
+                Block::Unknown { .. } => Some(block),
+                Block::GraphicRendering(GraphicRenderingBlock::Image(mut image)) => {
+                    if animated || transparent {
+                        flattened.take()
+                    } else {
+                        image.graphic_control_extension = None;
+                        Some(Block::GraphicRendering(GraphicRenderingBlock::Image(image)))
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+        for loss in &losses {
+            info!("Converting to GIF87a: {}", loss);
+        }
+        Ok(Conversion { gif, losses })
+    }
+
+    fn images(&self) -> impl Iterator<Item = &ImageDescriptor<'a>> {
+        self.graphic_rendering_blocks()
+            .filter_map(|block| match block {
+                GraphicRenderingBlock::Image(image) => Some(image),
+                GraphicRenderingBlock::PlainText(_) => None,
+            })
+    }
+}
+
+/// Whether dropping `ext` changes nothing, as it asks for what a viewer does without it.
+fn is_default(ext: &GraphicControlExtension) -> bool {
+    ext.delay_time == 0
+        && ext.disposal_method() == DisposalMethod::Unspecified
+        && ext.user_input_flag() == 0
+        && ext.transparent_color_flag() == 0
+}
+
+/// Clears the bits GIF87a reserves: the sort flag of the logical screen descriptor, and the sort
+/// flag and the 2 bits after it of image descriptors.
+fn clear_reserved_bits(gif: &mut GifData) {
+    gif.logical_screen_descriptor.set_sort_flag(false);
+    for block in &mut gif.blocks {
+        if let Block::GraphicRendering(GraphicRenderingBlock::Image(image)) = block {
+            crate::set_bits(&mut image.packed_fields, 3, 3, 0);
+        }
+    }
+}
+
+/// Turns the RGBA `pixels` of a composited frame into an image covering the logical screen,
+/// replacing the global color table of `gif` with the colors of the frame.
+fn flatten(gif: &mut GifData, pixels: &[u8]) -> Result<ImageDescriptor<'static>, GifError> {
+    let lsd = &mut gif.logical_screen_descriptor;
+    let background = lsd
+        .global_color_table
+        .as_ref()
+        .filter(|table| (lsd.background_color_index as usize) < table.len())
+        .map_or([0, 0, 0], |table| {
+            let pixel = table.get_pixel(lsd.background_color_index as usize);
+            [pixel[0], pixel[1], pixel[2]]
+        });
+    let mut palette = Palette::default();
+    palette.index(background);
+    let indices: Vec<u8> = pixels
+        .chunks(4)
+        .map(|pixel| match pixel {
+            [r, g, b, 0xff] => palette.index([*r, *g, *b]),
+            _ => palette.index(background),
+        })
+        .collect();
+
+    let (table, size_bits) = palette.color_table();
+    lsd.set_global_color_table(Some(table));
+    lsd.background_color_index = 0;
+    let image_data = TableBasedImageData::compress(&indices, (size_bits + 1).max(2))?;
+    Ok(ImageDescriptor::new(
+        0,
+        0,
+        lsd.logical_screen_width,
+        lsd.logical_screen_height,
+        image_data,
+    ))
+}
+
+/// The colors of a flattened frame, in order of appearance.
+#[derive(Default)]
+struct Palette {
+    colors: Vec<[u8; 3]>,
+    indices: HashMap<[u8; 3], u8>,
+}
+
+impl Palette {
+    /// The index of `color`, which is added if there's room for it and replaced by the closest
+    /// color otherwise. The first frame, the only one flattened, has at most 257 colors: the
+    /// background color and the ones of the color table of its image. So this is only needed
+    /// for the last one.
+    fn index(&mut self, color: [u8; 3]) -> u8 {
+        if let Some(&index) = self.indices.get(&color) {
+            return index;
+        }
+        if self.colors.len() < 256 {
+            let index = self.colors.len() as u8;
+            self.colors.push(color);
+            self.indices.insert(color, index);
+            return index;
+        }
+        let distance = |other: &[u8; 3]| -> u32 {
+            color
+                .iter()
+                .zip(other)
+                .map(|(&a, &b)| (a as i32 - b as i32).pow(2) as u32)
+                .sum()
+        };
+        let index = (0..self.colors.len())
+            .min_by_key(|&index| distance(&self.colors[index]))
+            .unwrap() as u8;
+        self.indices.insert(color, index);
+        index
+    }
+
+    /// The smallest color table holding every color, padded with black, and its size bits.
+    fn color_table(&self) -> (ColorTable<'static>, u8) {
+        let len = self.colors.len().next_power_of_two().max(2);
+        let mut pixels: Vec<u8> = self.colors.iter().flatten().copied().collect();
+        pixels.resize(len * 3, 0);
+        let table = ColorTable::new(pixels).expect("the size is a power of 2 between 2 and 256");
+        let size_bits = table.size_bits();
+        (table, size_bits)
+    }
+}
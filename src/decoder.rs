@@ -1,119 +1,506 @@
 use crate::{
-    ApplicationExtension, ColorTable, CommentExtension, Context, DataSubBlock, DataSubBlocks,
-    ExtensionBlock, GifData, GraphicControlExtension, GraphicRenderingBlock, ImageDescriptor,
-    LogicalScreenDescriptor, PlainTextExtension, TableBasedImageData, Version, SIGNATURE, TRAILER,
+    ApplicationExtension, Block, ColorTable, CommentExtension, Context, DataSubBlock,
+    DataSubBlocks, ExtensionBlock, GifData, GifError, GraphicControlExtension,
+    GraphicRenderingBlock, ImageDescriptor, LogicalScreenDescriptor, PlainTextExtension,
+    ReceivedSubBlocks, Span, TableBasedImageData, Version, SIGNATURE, TRAILER,
 };
-use anyhow::{anyhow, bail};
-use log::{debug, error, info};
+use log::{debug, info, warn};
+use std::{
+    borrow::Cow,
+    io::{self, BufReader, Read},
+};
+
+mod options;
+mod push;
+
+pub use options::{DecodeOptions, Limit, UnknownExtensions};
+pub use push::{Event, PushDecoder};
+
+/// Decodes a whole GIF data stream held in memory. The decoded blocks borrow from `bytes`.
+///
+/// When decoding leniently the warnings are only logged, see [`decode_with_warnings`].
+pub fn decode<'a>(bytes: &'a [u8], options: &DecodeOptions) -> Result<GifData<'a>, GifError> {
+    decode_with_warnings(bytes, options).map(|(gif, _)| gif)
+}
+
+/// Like [`decode`], also returning the deviations from the spec recovered from when decoding
+/// leniently (see [`DecodeOptions::lenient`]), each as the error it would have caused otherwise.
+pub fn decode_with_warnings<'a>(
+    bytes: &'a [u8],
+    options: &DecodeOptions,
+) -> Result<(GifData<'a>, Vec<GifError>), GifError> {
+    decode_data_stream(bytes, options).map(|(gif, warnings, _)| (gif, warnings))
+}
+
+/// Like [`decode_with_warnings`], also returning the offset of the first byte after the data
+/// stream, anything from there on being ignored.
+pub(crate) fn decode_data_stream<'a>(
+    bytes: &'a [u8],
+    options: &DecodeOptions,
+) -> Result<(GifData<'a>, Vec<GifError>, usize), GifError> {
+    let mut cx = Context::new(options);
+    let mut bytes = bytes;
+    let (version, logical_screen_descriptor) = decode_header(&mut cx, &mut bytes)?;
 
-pub fn decode(bytes: &[u8], discard_comments: bool) -> anyhow::Result<GifData> {
-    let mut cx = Context::default();
+    let mut blocks = Vec::new();
+    while let Some(block) = decode_block(&mut cx, &mut bytes)? {
+        blocks.push(block);
+    }
 
-    if &bytes[cx.offset..cx.offset + 3] != SIGNATURE {
-        bail!(
-            "invalid signature: expected '{:x?}', received '{:x?}'",
-            SIGNATURE,
-            &bytes[cx.offset..cx.offset + 3]
+    let gif = GifData {
+        version,
+        logical_screen_descriptor,
+        blocks,
+        trailer: cx.trailer,
+    };
+    if cx.offset < bytes.len() {
+        info!(
+            "Ignoring {} bytes after the trailer",
+            bytes.len() - cx.offset
         );
     }
-    cx.offset += 3;
+    Ok((gif, cx.warnings, cx.offset))
+}
+
+/// Decodes a GIF data stream from a reader, one block at a time.
+///
+/// Only the block being decoded is held in memory and every block is owned, so arbitrarily
+/// large data streams can be processed. The reader is buffered internally.
+pub struct StreamDecoder<R: Read> {
+    cx: Context<'static>,
+    source: ReadSource<BufReader<R>>,
+    version: Version,
+    logical_screen_descriptor: LogicalScreenDescriptor<'static>,
+    finished: bool,
+}
+
+impl<R: Read> StreamDecoder<R> {
+    /// Reads the header and the logical screen descriptor.
+    pub fn new(reader: R, options: &DecodeOptions) -> Result<Self, GifError> {
+        let mut cx = Context::new(options);
+        let mut source = ReadSource {
+            reader: BufReader::new(reader),
+        };
+        let (version, logical_screen_descriptor) = decode_header(&mut cx, &mut source)?;
+        Ok(Self {
+            cx,
+            source,
+            version,
+            logical_screen_descriptor,
+            finished: false,
+        })
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    pub fn logical_screen_descriptor(&self) -> &LogicalScreenDescriptor<'static> {
+        &self.logical_screen_descriptor
+    }
+
+    /// The deviations from the spec recovered from so far when decoding leniently.
+    pub fn warnings(&self) -> &[GifError] {
+        &self.cx.warnings
+    }
+
+    /// Decodes the next block, returning `None` once the trailer has been read.
+    pub fn next_block(&mut self) -> Result<Option<Block<'static>>, GifError> {
+        if self.finished {
+            return Ok(None);
+        }
+        let block = decode_block(&mut self.cx, &mut self.source);
+        if !matches!(block, Ok(Some(_))) {
+            self.finished = true;
+        }
+        block
+    }
+}
+
+impl<R: Read> Iterator for StreamDecoder<R> {
+    type Item = Result<Block<'static>, GifError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
+
+/// Where the decoder gets its bytes from.
+pub(crate) trait Source<'a> {
+    /// Returns the `len` bytes found at `offset`, which is always right after the bytes returned
+    /// by the previous call.
+    fn read(&mut self, offset: usize, len: usize) -> Result<Cow<'a, [u8]>, GifError>;
+
+    /// Like [`Source::read`], but returns the bytes left when there are fewer than `len`.
+    fn read_available(&mut self, offset: usize, len: usize) -> Result<Cow<'a, [u8]>, GifError>;
+}
+
+impl<'a> Source<'a> for &'a [u8] {
+    fn read(&mut self, offset: usize, len: usize) -> Result<Cow<'a, [u8]>, GifError> {
+        let available = self.len().saturating_sub(offset);
+        if available < len {
+            return Err(GifError::UnexpectedEof {
+                offset: offset + available,
+                needed: len - available,
+            });
+        }
+        Ok(Cow::Borrowed(&self[offset..offset + len]))
+    }
+
+    fn read_available(&mut self, offset: usize, len: usize) -> Result<Cow<'a, [u8]>, GifError> {
+        let start = offset.min(self.len());
+        Ok(Cow::Borrowed(&self[start..(start + len).min(self.len())]))
+    }
+}
+
+pub(crate) struct ReadSource<R> {
+    reader: R,
+}
 
-    let version = Version::decode(&mut cx, bytes)?;
+impl<R: Read> Source<'static> for ReadSource<R> {
+    fn read(&mut self, offset: usize, len: usize) -> Result<Cow<'static, [u8]>, GifError> {
+        let data = self.read_available(offset, len)?;
+        if data.len() < len {
+            return Err(GifError::UnexpectedEof {
+                offset: offset + data.len(),
+                needed: len - data.len(),
+            });
+        }
+        Ok(data)
+    }
+
+    fn read_available(
+        &mut self,
+        offset: usize,
+        len: usize,
+    ) -> Result<Cow<'static, [u8]>, GifError> {
+        let mut data = vec![0; len];
+        let mut filled = 0;
+        while filled < len {
+            match self.reader.read(&mut data[filled..]) {
+                Ok(0) => {
+                    data.truncate(filled);
+                    break;
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    return Err(GifError::Io {
+                        offset: offset + filled,
+                        kind: e.kind(),
+                        message: e.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(Cow::Owned(data))
+    }
+}
+
+fn decode_header<'a, S: Source<'a>>(
+    cx: &mut Context,
+    bytes: &mut S,
+) -> Result<(Version, LogicalScreenDescriptor<'a>), GifError> {
+    let version = decode_signature_and_version(cx, bytes)?;
+    let logical_screen_descriptor = decode_logical_screen_descriptor(cx, bytes)?;
+    Ok((version, logical_screen_descriptor))
+}
+
+fn decode_signature_and_version<'a, S: Source<'a>>(
+    cx: &mut Context,
+    bytes: &mut S,
+) -> Result<Version, GifError> {
+    let signature = cx.read_bytes(bytes, 3)?;
+    if *signature != *SIGNATURE {
+        return Err(GifError::InvalidSignature {
+            offset: cx.offset - 3,
+            found: signature.into_owned(),
+        });
+    }
+
+    let version = Version::decode(cx, bytes)?;
     info!("GIF version: {:?}", version);
+    Ok(version)
+}
 
-    let logical_screen_descriptor = LogicalScreenDescriptor::decode(&mut cx, bytes);
+fn decode_logical_screen_descriptor<'a, S: Source<'a>>(
+    cx: &mut Context,
+    bytes: &mut S,
+) -> Result<LogicalScreenDescriptor<'a>, GifError> {
+    let offset = cx.offset;
+    let logical_screen_descriptor = LogicalScreenDescriptor::decode(cx, bytes)?;
+    cx.options
+        .check_logical_screen_descriptor(offset, &logical_screen_descriptor)?;
     info!("Found logical screen descriptor");
     debug!(
         "[{:?}] Logical screen descriptor: {:?}",
         cx, logical_screen_descriptor
     );
+    Ok(logical_screen_descriptor)
+}
+
+/// Decodes blocks until one that belongs in [`GifData::blocks`] is found, returning `None` once
+/// the trailer has been read.
+fn decode_block<'a, S: Source<'a>>(
+    cx: &mut Context<'a>,
+    bytes: &mut S,
+) -> Result<Option<Block<'a>>, GifError> {
+    if let Some(block) = cx.pending_block.take() {
+        return Ok(Some(block));
+    }
+    if cx.trailer_found {
+        return Ok(None);
+    }
+    match decode_next_block(cx, bytes) {
+        Err(e @ GifError::UnexpectedEof { .. }) if cx.options.lenient => {
+            cx.warn(e);
+            info!("Treating the end of the data stream as the trailer");
+            cx.trailer_found = true;
+            Ok(cx
+                .graphic_control_extension
+                .take()
+                .map(Block::GraphicControl))
+        }
+        result => result,
+    }
+}
 
-    let mut application_extensions = Vec::new();
-    let mut graphic_rendering_blocks = Vec::new();
-    let mut comment_extensions = Vec::new();
+fn decode_next_block<'a, S: Source<'a>>(
+    cx: &mut Context<'a>,
+    bytes: &mut S,
+) -> Result<Option<Block<'a>>, GifError> {
     loop {
-        match bytes[cx.offset] {
+        let byte = match cx.read_u8(bytes)? {
+            byte @ (ExtensionBlock::INTRODUCER | ImageDescriptor::SEPARATOR | TRAILER) => byte,
+            byte => skip_garbage(cx, bytes, byte)?,
+        };
+        match byte {
             ExtensionBlock::INTRODUCER => {
-                cx.offset += 1;
-                if let Some(extension_block) =
-                    ExtensionBlock::decode(&mut cx, bytes, discard_comments)?
-                {
+                if let Some(extension_block) = ExtensionBlock::decode(cx, bytes)? {
                     let extension_block_s: &'static str = (&extension_block).into();
                     info!("Found {} extension block", extension_block_s);
                     debug!("[{:?}] Extension block: {:?}", cx, extension_block);
 
-                    match extension_block {
-                        ExtensionBlock::GraphicControl(ext) => {
-                            cx.graphic_control_extension = Some(ext);
-                        }
-                        ExtensionBlock::Application(ext) => {
-                            application_extensions.push(ext);
-                        }
-                        ExtensionBlock::PlainText(ext) => {
-                            if logical_screen_descriptor.global_color_table.is_none() {
-                                error!("Discarding plain text extension block because no global color table is available");
-                                continue;
-                            }
-                            graphic_rendering_blocks.push(GraphicRenderingBlock::PlainText(ext));
-                        }
-                        ExtensionBlock::Comment(ext) => {
-                            comment_extensions.push(ext);
-                        }
+                    if let Some(block) = handle_extension_block(cx, extension_block) {
+                        return Ok(Some(block));
                     }
                 }
             }
             ImageDescriptor::SEPARATOR => {
-                cx.offset += 1;
-                let image_descriptor = ImageDescriptor::decode(&mut cx, bytes);
+                let image_descriptor = ImageDescriptor::decode(cx, bytes)?;
                 info!("Found image descriptor");
                 debug!("[{:?}] Image descriptor: {:?}", cx, image_descriptor);
-                graphic_rendering_blocks.push(GraphicRenderingBlock::Image(image_descriptor));
+                return Ok(Some(Block::GraphicRendering(GraphicRenderingBlock::Image(
+                    image_descriptor,
+                ))));
             }
-            TRAILER => {
+            // The trailer, as `skip_garbage` only returns bytes that introduce a block.
+            _ => {
                 info!("End of GIF data stream");
-                break;
+                cx.trailer_found = true;
+                cx.trailer = cx.span_from(cx.offset - 1);
+                if let Some(ext) = cx.graphic_control_extension.take() {
+                    info!("Keeping a graphic control extension followed by the trailer");
+                    return Ok(Some(Block::GraphicControl(ext)));
+                }
+                return Ok(None);
             }
-            b => bail!("unknown byte 0x{:x} at offset {}", b, cx.offset),
         }
     }
+}
 
-    Ok(GifData {
-        version,
-        logical_screen_descriptor,
-        application_extensions,
-        graphic_rendering_blocks,
-        comment_extensions,
-    })
+/// Fails on `byte`, found where a block should start, unless decoding leniently: then skips it
+/// along with the bytes following it, up to the first one that introduces a block.
+fn skip_garbage<'a, S: Source<'a>>(
+    cx: &mut Context,
+    bytes: &mut S,
+    byte: u8,
+) -> Result<u8, GifError> {
+    let e = GifError::UnknownBlock {
+        offset: cx.offset - 1,
+        byte,
+    };
+    if !cx.options.lenient {
+        return Err(e);
+    }
+    cx.warn(e);
+    loop {
+        let byte = cx.read_u8(bytes)?;
+        if let ExtensionBlock::INTRODUCER | ImageDescriptor::SEPARATOR | TRAILER = byte {
+            return Ok(byte);
+        }
+    }
+}
+
+/// Returns the block an extension block turns into, if any: graphic control extensions are held
+/// in `cx` until it's known whether they're followed by a graphic rendering block.
+fn handle_extension_block<'a>(
+    cx: &mut Context<'a>,
+    extension_block: ExtensionBlock<'a>,
+) -> Option<Block<'a>> {
+    match extension_block {
+        ExtensionBlock::GraphicControl(ext) => {
+            let previous = cx.graphic_control_extension.replace(ext)?;
+            info!("Keeping a graphic control extension followed by another one");
+            Some(Block::GraphicControl(previous))
+        }
+        ExtensionBlock::Application(ext) => {
+            Some(cx.orphan_graphic_control_extension(Block::Application(ext)))
+        }
+        ExtensionBlock::PlainText(ext) => Some(Block::GraphicRendering(
+            GraphicRenderingBlock::PlainText(ext),
+        )),
+        ExtensionBlock::Comment(ext) => {
+            Some(cx.orphan_graphic_control_extension(Block::Comment(ext)))
+        }
+        ExtensionBlock::Unknown { label, data, span } => {
+            Some(cx.orphan_graphic_control_extension(Block::Unknown { label, data, span }))
+        }
+    }
+}
+
+impl<'c> Context<'c> {
+    fn new(options: &DecodeOptions) -> Self {
+        Self {
+            options: options.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Records a deviation from the spec that lenient decoding recovered from.
+    fn warn(&mut self, warning: GifError) {
+        warn!("Recovering from: {}", warning);
+        self.warnings.push(warning);
+    }
+
+    /// Checks the image descriptor found at `offset` (its separator) against the limits, then
+    /// counts it.
+    fn check_image_descriptor(
+        &mut self,
+        offset: usize,
+        image: &ImageDescriptor,
+    ) -> Result<(), GifError> {
+        self.options
+            .check_image_descriptor(offset, image, self.frames, self.total_pixels)?;
+        self.frames += 1;
+        self.total_pixels += image.image_width as u64 * image.image_height as u64;
+        Ok(())
+    }
+
+    /// Returns `block`, unless a graphic control extension is waiting for a graphic rendering
+    /// block: then that extension is returned as a block of its own and `block` is kept for the
+    /// next call to [`decode_block`].
+    fn orphan_graphic_control_extension(&mut self, block: Block<'c>) -> Block<'c> {
+        match self.graphic_control_extension.take() {
+            Some(ext) => {
+                info!(
+                    "Keeping a graphic control extension not followed by a graphic rendering block"
+                );
+                self.pending_block = Some(block);
+                Block::GraphicControl(ext)
+            }
+            None => block,
+        }
+    }
+
+    /// The span from `start` to the current offset.
+    pub(crate) fn span_from(&self, start: usize) -> Option<Span> {
+        Some(Span {
+            start,
+            end: self.offset,
+        })
+    }
+
+    /// Returns the next `len` bytes and advances past them, failing instead of panicking when
+    /// the data stream is too short.
+    pub(crate) fn read_bytes<'a, S: Source<'a>>(
+        &mut self,
+        bytes: &mut S,
+        len: usize,
+    ) -> Result<Cow<'a, [u8]>, GifError> {
+        let data = bytes.read(self.offset, len)?;
+        self.offset += len;
+        Ok(data)
+    }
+
+    /// Reads up to `len` bytes, fewer only at the end of the data stream.
+    fn read_available_bytes<'a, S: Source<'a>>(
+        &mut self,
+        bytes: &mut S,
+        len: usize,
+    ) -> Result<Cow<'a, [u8]>, GifError> {
+        let data = bytes.read_available(self.offset, len)?;
+        self.offset += data.len();
+        Ok(data)
+    }
+
+    pub(crate) fn read_u8<'a, S: Source<'a>>(&mut self, bytes: &mut S) -> Result<u8, GifError> {
+        Ok(self.read_bytes(bytes, 1)?[0])
+    }
+
+    /// Unsigned words are stored little-endian.
+    pub(crate) fn read_u16<'a, S: Source<'a>>(&mut self, bytes: &mut S) -> Result<u16, GifError> {
+        let data = self.read_bytes(bytes, 2)?;
+        Ok(u16::from_le_bytes([data[0], data[1]]))
+    }
+
+    /// Reads the size byte of a fixed-size block, checking it against the one mandated by the
+    /// spec.
+    fn read_block_size<'a, S: Source<'a>>(
+        &mut self,
+        bytes: &mut S,
+        expected: u8,
+    ) -> Result<(), GifError> {
+        let block_size = self.read_u8(bytes)?;
+        if block_size != expected {
+            return Err(GifError::BadBlockSize {
+                offset: self.offset - 1,
+                expected,
+                found: block_size,
+            });
+        }
+        Ok(())
+    }
 }
 
 impl Version {
-    fn decode(cx: &mut Context, version: &[u8]) -> anyhow::Result<Self> {
-        let s = match &version[cx.offset..cx.offset + 3] {
+    fn decode<'a, S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        let version = cx.read_bytes(bytes, 3)?;
+        match &*version {
             b"87a" => Ok(Self::V87a),
             b"89a" => Ok(Self::V89a),
-            v => Err(anyhow!(
-                "invalid GIF version at offset {}. Expected either b\"v87a\" or b\"89a\", got '{:?}'",
-                cx.offset,
-                v
-            )),
-        };
-        cx.offset += 3;
-        s
+            v => Err(GifError::InvalidVersion {
+                offset: cx.offset - 3,
+                found: v.to_vec(),
+            }),
+        }
+    }
+}
+
+impl<'a> ColorTable<'a> {
+    fn decode<S: Source<'a>>(
+        cx: &mut Context,
+        bytes: &mut S,
+        size_bits: u8,
+    ) -> Result<Self, GifError> {
+        let start = cx.offset;
+        let len = 3 * 2usize.pow(size_bits as u32 + 1u32);
+        let pixels = cx.read_bytes(bytes, len)?;
+        Ok(Self {
+            pixels,
+            span: cx.span_from(start),
+        })
     }
 }
 
 impl<'a> LogicalScreenDescriptor<'a> {
-    fn decode(cx: &mut Context, bytes: &'a [u8]) -> Self {
-        let logical_screen_width = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let logical_screen_height = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let packed_fields = bytes[cx.offset];
-        cx.offset += 1;
-        let background_color_index = bytes[cx.offset];
-        cx.offset += 1;
-        let pixel_aspect_ratio = bytes[cx.offset];
-        cx.offset += 1;
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        let start = cx.offset;
+        let logical_screen_width = cx.read_u16(bytes)?;
+        let logical_screen_height = cx.read_u16(bytes)?;
+        let packed_fields = cx.read_u8(bytes)?;
+        let background_color_index = cx.read_u8(bytes)?;
+        let pixel_aspect_ratio = cx.read_u8(bytes)?;
         let mut s = Self {
             logical_screen_width,
             logical_screen_height,
@@ -121,141 +508,223 @@ impl<'a> LogicalScreenDescriptor<'a> {
             background_color_index,
             pixel_aspect_ratio,
             global_color_table: None,
+            span: cx.span_from(start),
         };
 
         if s.global_color_table_flag() == 1 {
-            let global_color_table_size = 3 * 2usize.pow(s.global_color_table_size() as u32 + 1u32);
+            let global_color_table = ColorTable::decode(cx, bytes, s.global_color_table_size())?;
             info!(
                 "Global color table found: size -> {}, number of pixels -> {}",
-                global_color_table_size,
-                global_color_table_size / 3
+                global_color_table.pixels.len(),
+                global_color_table.pixels.len() / 3
             );
-            s.global_color_table = Some(ColorTable {
-                pixels: &bytes[cx.offset..cx.offset + global_color_table_size],
-            });
+            s.global_color_table = Some(global_color_table);
             debug!("[{:?}] Global color table: {:?}", cx, s.global_color_table);
-            cx.offset += global_color_table_size;
         };
-        s
+        Ok(s)
     }
 }
 
 impl<'a> ExtensionBlock<'a> {
-    fn decode(
-        cx: &mut Context,
-        bytes: &'a [u8],
-        discard_comments: bool,
-    ) -> anyhow::Result<Option<Self>> {
-        let label = bytes[cx.offset];
-        cx.offset += 1;
+    /// Decodes the extension following the extension introducer that has just been read.
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Option<Self>, GifError> {
+        let start = cx.offset - 1;
+        let label = cx.read_u8(bytes)?;
         match label {
-            GraphicControlExtension::LABEL => Ok(Some(Self::GraphicControl(
-                GraphicControlExtension::decode(cx, bytes)?,
-            ))),
+            GraphicControlExtension::LABEL => {
+                let mut ext = GraphicControlExtension::decode(cx, bytes)?;
+                ext.span = cx.span_from(start);
+                if cx.options.discard_graphic_control_extensions {
+                    Ok(None)
+                } else {
+                    Ok(Some(Self::GraphicControl(ext)))
+                }
+            }
             CommentExtension::LABEL => {
-                if discard_comments {
+                let mut ext = CommentExtension::decode(cx, bytes)?;
+                ext.span = cx.span_from(start);
+                if cx.options.discard_comments {
+                    Ok(None)
+                } else {
+                    Ok(Some(Self::Comment(ext)))
+                }
+            }
+            PlainTextExtension::LABEL => {
+                let mut ext = PlainTextExtension::decode(cx, bytes)?;
+                ext.span = cx.span_from(start);
+                // Along with its graphic control extension.
+                if cx.options.discard_plain_text_extensions {
                     Ok(None)
                 } else {
-                    Ok(Some(Self::Comment(CommentExtension::decode(cx, bytes))))
+                    Ok(Some(Self::PlainText(ext)))
                 }
             }
-            PlainTextExtension::LABEL => Ok(Some(Self::PlainText(PlainTextExtension::decode(
-                cx, bytes,
-            )?))),
-            ApplicationExtension::LABEL => Ok(Some(Self::Application(
-                ApplicationExtension::decode(cx, bytes)?,
-            ))),
-            label => Err(anyhow!(
-                "invalid extension block label '{}' at offset {}",
-                label,
-                cx.offset
-            )),
+            ApplicationExtension::LABEL => {
+                let mut ext = ApplicationExtension::decode(cx, bytes)?;
+                ext.span = cx.span_from(start);
+                if cx.options.discard_application_extensions {
+                    Ok(None)
+                } else {
+                    Ok(Some(Self::Application(ext)))
+                }
+            }
+            label => match cx.options.unknown_extensions {
+                UnknownExtensions::Keep => {
+                    info!(
+                        "Unknown extension block label '0x{:x}' at offset {}, keeping it as it is",
+                        label,
+                        cx.offset - 1
+                    );
+                    let data = DataSubBlocks::decode(cx, bytes)?;
+                    Ok(Some(Self::Unknown {
+                        label,
+                        data,
+                        span: cx.span_from(start),
+                    }))
+                }
+                UnknownExtensions::Discard => {
+                    info!(
+                        "Unknown extension block label '0x{:x}' at offset {}, discarding it",
+                        label,
+                        cx.offset - 1
+                    );
+                    DataSubBlocks::decode(cx, bytes)?;
+                    Ok(None)
+                }
+                UnknownExtensions::Reject => Err(GifError::UnknownLabel {
+                    offset: cx.offset - 1,
+                    label,
+                }),
+            },
         }
     }
 }
 
 impl<'a> DataSubBlock<'a> {
-    fn decode(cx: &mut Context, bytes: &'a [u8]) -> Option<Self> {
-        let block_size = bytes[cx.offset];
-        cx.offset += 1;
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Option<Self>, GifError> {
+        let start = cx.offset;
+        let block_size = cx.read_u8(bytes)?;
         if block_size == Self::BLOCK_TERMINATOR {
-            return None;
+            return Ok(None);
         }
-        let data = &bytes[cx.offset..cx.offset + block_size as usize];
-        cx.offset += block_size as usize;
-        Some(Self { block_size, data })
+        // When decoding leniently a truncated sub-block comes with the bytes received, fewer than
+        // its size.
+        let data = if cx.options.lenient && !cx.incremental {
+            cx.read_available_bytes(bytes, block_size as usize)?
+        } else {
+            cx.read_bytes(bytes, block_size as usize)?
+        };
+        Ok(Some(Self {
+            block_size,
+            data,
+            span: cx.span_from(start),
+        }))
     }
 }
 
 impl<'a> DataSubBlocks<'a> {
-    fn decode(cx: &mut Context, bytes: &'a [u8]) -> Self {
+    /// When decoding leniently a truncated data stream is treated as ending with the sub-blocks
+    /// decoded so far, the last one with the bytes received, which is how a truncated last image
+    /// gets to be kept.
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        if cx.incremental {
+            return Self::resume(cx, bytes);
+        }
         let mut blocks = Vec::new();
         loop {
-            if let Some(block) = DataSubBlock::decode(cx, bytes) {
-                blocks.push(block);
-            } else {
-                break;
+            let eof = match DataSubBlock::decode(cx, bytes) {
+                Ok(Some(block)) if block.data.len() == block.block_size as usize => {
+                    blocks.push(block);
+                    continue;
+                }
+                Ok(Some(mut block)) => {
+                    let received = block.data.len();
+                    let e = GifError::UnexpectedEof {
+                        offset: cx.offset,
+                        needed: block.block_size as usize - received,
+                    };
+                    if received > 0 {
+                        block.block_size = received as u8;
+                        blocks.push(block);
+                    }
+                    e
+                }
+                Ok(None) => break,
+                Err(e @ GifError::UnexpectedEof { .. }) if cx.options.lenient => e,
+                Err(e) => return Err(e),
+            };
+            cx.warn(eof);
+            info!("Treating the end of the data stream as the trailer");
+            cx.trailer_found = true;
+            break;
+        }
+        Ok(Self { blocks })
+    }
+
+    /// Decodes the sub-blocks of a data stream being pushed, starting after the ones received by
+    /// the previous attempt, if any. Running out of bytes keeps the complete ones in the context,
+    /// so that they aren't decoded again once more bytes arrive.
+    fn resume<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        let start = cx.offset;
+        let mut received = match cx.received_sub_blocks.take() {
+            Some(received) if received.start == start => {
+                cx.offset = received.end;
+                received
+            }
+            _ => ReceivedSubBlocks {
+                start,
+                end: start,
+                blocks: Vec::new(),
+            },
+        };
+        loop {
+            match DataSubBlock::decode(cx, bytes) {
+                Ok(Some(block)) => {
+                    received.blocks.push(block.into_owned());
+                    received.end = cx.offset;
+                }
+                Ok(None) => {
+                    return Ok(Self {
+                        blocks: received.blocks,
+                    })
+                }
+                Err(e @ GifError::UnexpectedEof { .. }) => {
+                    cx.received_sub_blocks = Some(received);
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
             }
         }
-        Self { blocks }
     }
 }
 
 impl<'a> ApplicationExtension<'a> {
-    fn decode(cx: &mut Context, bytes: &'a [u8]) -> anyhow::Result<Self> {
-        let block_size = bytes[cx.offset];
-        if block_size != Self::BLOCK_SIZE {
-            bail!(
-                "invalid block size at offset {}: expected '{}', got '{}'",
-                cx.offset,
-                Self::BLOCK_SIZE,
-                block_size
-            );
-        }
-        cx.offset += 1;
-        let identifier = &bytes[cx.offset..cx.offset + 8];
-        cx.offset += 8;
-        let authentication_code = &bytes[cx.offset..cx.offset + 3];
-        cx.offset += 3;
-        let data = DataSubBlocks::decode(cx, bytes);
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        cx.read_block_size(bytes, Self::BLOCK_SIZE)?;
+        let identifier = cx.read_bytes(bytes, 8)?;
+        let authentication_code = cx.read_bytes(bytes, 3)?;
+        let data = DataSubBlocks::decode(cx, bytes)?;
         Ok(Self {
             identifier,
             authentication_code,
             data,
+            span: None,
         })
     }
 }
 
 impl<'a> PlainTextExtension<'a> {
-    fn decode(cx: &mut Context, bytes: &'a [u8]) -> anyhow::Result<Self> {
-        let block_size = bytes[cx.offset];
-        if block_size != Self::BLOCK_SIZE {
-            bail!(
-                "invalid block size at offset {}: expected '{}', got '{}'",
-                cx.offset,
-                Self::BLOCK_SIZE,
-                block_size
-            );
-        }
-        cx.offset += 1;
-        let text_grid_left_position =
-            ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let text_grid_top_position = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let text_grid_width = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let text_grid_height = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let character_cell_width = bytes[cx.offset];
-        cx.offset += 1;
-        let character_cell_height = bytes[cx.offset];
-        cx.offset += 1;
-        let text_foreground_color_index = bytes[cx.offset];
-        cx.offset += 1;
-        let text_background_color_index = bytes[cx.offset];
-        cx.offset += 1;
-        let data = DataSubBlocks::decode(cx, bytes);
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        cx.read_block_size(bytes, Self::BLOCK_SIZE)?;
+        let text_grid_left_position = cx.read_u16(bytes)?;
+        let text_grid_top_position = cx.read_u16(bytes)?;
+        let text_grid_width = cx.read_u16(bytes)?;
+        let text_grid_height = cx.read_u16(bytes)?;
+        let character_cell_width = cx.read_u8(bytes)?;
+        let character_cell_height = cx.read_u8(bytes)?;
+        let text_foreground_color_index = cx.read_u8(bytes)?;
+        let text_background_color_index = cx.read_u8(bytes)?;
+        let data = DataSubBlocks::decode(cx, bytes)?;
         Ok(Self {
             text_grid_left_position,
             text_grid_top_position,
@@ -267,107 +736,143 @@ impl<'a> PlainTextExtension<'a> {
             text_background_color_index,
             data,
             graphic_control_extension: cx.graphic_control_extension.take(),
+            span: None,
         })
     }
 }
 
 impl GraphicControlExtension {
-    fn decode(cx: &mut Context, bytes: &[u8]) -> anyhow::Result<Self> {
-        let block_size = bytes[cx.offset];
-        if block_size != Self::BLOCK_SIZE {
-            bail!(
-                "invalid block size at offset {}: expected '{}', got '{}'",
-                cx.offset,
-                Self::BLOCK_SIZE,
-                block_size
-            );
+    fn decode<'a, S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        if cx.options.lenient {
+            return Self::decode_leniently(cx, bytes);
         }
-        cx.offset += 1;
-        let packed_fields = bytes[cx.offset];
-        cx.offset += 1;
-        let delay_time = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let transparent_color_index = bytes[cx.offset];
-        cx.offset += 1;
-        if bytes[cx.offset] != DataSubBlock::BLOCK_TERMINATOR {
-            bail!(
-                "invalid block terminator at offset {}: expected '{}', got '{}'",
-                cx.offset,
-                DataSubBlock::BLOCK_TERMINATOR,
-                bytes[cx.offset]
-            );
+        cx.read_block_size(bytes, Self::BLOCK_SIZE)?;
+        let packed_fields = cx.read_u8(bytes)?;
+        let delay_time = cx.read_u16(bytes)?;
+        let transparent_color_index = cx.read_u8(bytes)?;
+        let block_terminator = cx.read_u8(bytes)?;
+        if block_terminator != DataSubBlock::BLOCK_TERMINATOR {
+            return Err(GifError::BadBlockTerminator {
+                offset: cx.offset - 1,
+                found: block_terminator,
+            });
         }
-        cx.offset += 1;
         Ok(Self {
             packed_fields,
             delay_time,
             transparent_color_index,
+            span: None,
+        })
+    }
+
+    /// Accepts any block size, using the fields that are there and defaulting the others to 0,
+    /// and any number of sub-blocks before the block terminator.
+    fn decode_leniently<'a, S: Source<'a>>(
+        cx: &mut Context,
+        bytes: &mut S,
+    ) -> Result<Self, GifError> {
+        let block_size = cx.read_u8(bytes)?;
+        if block_size != Self::BLOCK_SIZE {
+            cx.warn(GifError::BadBlockSize {
+                offset: cx.offset - 1,
+                expected: Self::BLOCK_SIZE,
+                found: block_size,
+            });
+        }
+        let mut fields = [0; Self::BLOCK_SIZE as usize];
+        let data = cx.read_bytes(bytes, block_size as usize)?;
+        let len = data.len().min(fields.len());
+        fields[..len].copy_from_slice(&data[..len]);
+
+        let offset = cx.offset;
+        let extra = DataSubBlocks::decode(cx, bytes)?;
+        if let Some(block) = extra.blocks.first() {
+            cx.warn(GifError::BadBlockTerminator {
+                offset,
+                found: block.block_size,
+            });
+        }
+        Ok(Self {
+            packed_fields: fields[0],
+            delay_time: u16::from_le_bytes([fields[1], fields[2]]),
+            transparent_color_index: fields[3],
+            span: None,
         })
     }
 }
 
 impl<'a> CommentExtension<'a> {
-    fn decode(cx: &mut Context, bytes: &'a [u8]) -> Self {
-        let data = DataSubBlocks::decode(cx, bytes);
-        Self { data }
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        let data = DataSubBlocks::decode(cx, bytes)?;
+        Ok(Self { data, span: None })
     }
 }
 
 impl<'a> ImageDescriptor<'a> {
-    fn decode(cx: &mut Context, bytes: &'a [u8]) -> Self {
-        let image_left_position = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let image_top_position = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let image_width = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let image_height = ((bytes[cx.offset] as u16) << 8) | bytes[cx.offset + 1] as u16;
-        cx.offset += 2;
-        let packed_fields = bytes[cx.offset];
-        cx.offset += 1;
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        let offset = cx.offset - 1;
+        let mut s = Self::decode_without_image_data(cx, bytes)?;
+        cx.check_image_descriptor(offset, &s)?;
+        s.image_data = TableBasedImageData::decode(cx, bytes)?;
+        s.graphic_control_extension = cx.graphic_control_extension.take();
+        Ok(s)
+    }
+
+    /// Decodes the fields and the local color table, leaving the image data empty and the
+    /// graphic control extension in `cx`.
+    fn decode_without_image_data<S: Source<'a>>(
+        cx: &mut Context,
+        bytes: &mut S,
+    ) -> Result<Self, GifError> {
+        let start = cx.offset - 1;
+        let image_left_position = cx.read_u16(bytes)?;
+        let image_top_position = cx.read_u16(bytes)?;
+        let image_width = cx.read_u16(bytes)?;
+        let image_height = cx.read_u16(bytes)?;
+        let packed_fields = cx.read_u8(bytes)?;
+        let span = cx.span_from(start);
 
         let local_color_table_flag = packed_fields >> 7;
         let local_color_table = if local_color_table_flag == 1 {
-            let local_color_table_size = (packed_fields << 5) >> 5;
-            let local_color_table_size = 3 * 2usize.pow(local_color_table_size as u32 + 1u32);
+            let local_color_table = ColorTable::decode(cx, bytes, (packed_fields << 5) >> 5)?;
             info!(
                 "Local color table found: size -> {}, number of pixels -> {}",
-                local_color_table_size,
-                local_color_table_size / 3
+                local_color_table.pixels.len(),
+                local_color_table.pixels.len() / 3
             );
-            let local_color_table = Some(ColorTable {
-                pixels: &bytes[cx.offset..cx.offset + local_color_table_size],
-            });
             debug!("[{:?}] Local color table: {:?}", cx, local_color_table);
-            cx.offset += local_color_table_size;
-            local_color_table
+            Some(local_color_table)
         } else {
             None
         };
 
-        let image_data = TableBasedImageData::decode(cx, bytes);
-
-        Self {
+        Ok(Self {
             image_left_position,
             image_top_position,
             image_width,
             image_height,
             packed_fields,
             local_color_table,
-            image_data,
-            graphic_control_extension: cx.graphic_control_extension.take(),
-        }
+            image_data: TableBasedImageData {
+                lzw_minimum_code_size: 0,
+                image_data: DataSubBlocks { blocks: Vec::new() },
+                span: None,
+            },
+            graphic_control_extension: None,
+            span,
+        })
     }
 }
 
 impl<'a> TableBasedImageData<'a> {
-    fn decode(cx: &mut Context, bytes: &'a [u8]) -> Self {
-        let lzw_minimum_code_size = bytes[cx.offset];
-        cx.offset += 1;
-        let image_data = DataSubBlocks::decode(cx, bytes);
-        Self {
+    fn decode<S: Source<'a>>(cx: &mut Context, bytes: &mut S) -> Result<Self, GifError> {
+        let start = cx.offset;
+        let lzw_minimum_code_size = cx.read_u8(bytes)?;
+        let image_data = DataSubBlocks::decode(cx, bytes)?;
+        Ok(Self {
             lzw_minimum_code_size,
             image_data,
-        }
+            span: cx.span_from(start),
+        })
     }
 }
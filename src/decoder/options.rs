@@ -0,0 +1,265 @@
+//! What the decoders keep and how much they're willing to decode.
+
+use crate::{render, GifError, ImageDescriptor, LogicalScreenDescriptor};
+use std::fmt;
+
+/// What to do with extensions whose label isn't known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownExtensions {
+    /// Keep them as [`crate::Block::Unknown`].
+    Keep,
+    /// Drop them.
+    Discard,
+    /// Fail with [`GifError::UnknownLabel`].
+    Reject,
+}
+
+/// A limit set with [`DecodeOptions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    LogicalScreenWidth,
+    LogicalScreenHeight,
+    FrameWidth,
+    FrameHeight,
+    Frames,
+    /// The color indices of a single image, bounding what LZW decompression can output.
+    FramePixels,
+    /// The color indices of all of the images.
+    TotalPixels,
+    /// The pixels of the canvas the images are composited onto, see
+    /// [`crate::GifData::frames_with_options`].
+    CanvasPixels,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::LogicalScreenWidth => "logical screen width",
+            Self::LogicalScreenHeight => "logical screen height",
+            Self::FrameWidth => "frame width",
+            Self::FrameHeight => "frame height",
+            Self::Frames => "number of frames",
+            Self::FramePixels => "number of pixels of a frame",
+            Self::TotalPixels => "total number of pixels",
+            Self::CanvasPixels => "number of pixels of the canvas",
+        })
+    }
+}
+
+/// Options shared by every decoder, built by chaining setters on [`DecodeOptions::new`].
+///
+/// By default every block is kept and nothing is limited. Limits are checked against the sizes
+/// declared by the data stream, as soon as they're decoded and before reading any image data, so
+/// that untrusted data streams can be rejected before they use any significant amount of memory.
+///
+/// There's no limit on the LZW output of an image apart from
+/// [`DecodeOptions::max_frame_pixels`]: decompression fails with
+/// [`GifError::ImageDataTooLong`] rather than output more color indices than the image declares.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    pub(crate) discard_comments: bool,
+    pub(crate) discard_application_extensions: bool,
+    pub(crate) discard_graphic_control_extensions: bool,
+    pub(crate) discard_plain_text_extensions: bool,
+    pub(crate) unknown_extensions: UnknownExtensions,
+    pub(crate) lenient: bool,
+    max_logical_screen_size: Option<(u16, u16)>,
+    max_frame_size: Option<(u16, u16)>,
+    max_frames: Option<usize>,
+    max_frame_pixels: Option<u64>,
+    max_total_pixels: Option<u64>,
+    max_canvas_pixels: Option<u64>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            discard_comments: false,
+            discard_application_extensions: false,
+            discard_graphic_control_extensions: false,
+            discard_plain_text_extensions: false,
+            unknown_extensions: UnknownExtensions::Keep,
+            lenient: false,
+            max_logical_screen_size: None,
+            max_frame_size: None,
+            max_frames: None,
+            max_frame_pixels: None,
+            max_total_pixels: None,
+            max_canvas_pixels: None,
+        }
+    }
+}
+
+impl DecodeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn discard_comments(mut self, discard_comments: bool) -> Self {
+        self.discard_comments = discard_comments;
+        self
+    }
+
+    pub fn discard_application_extensions(mut self, discard_application_extensions: bool) -> Self {
+        self.discard_application_extensions = discard_application_extensions;
+        self
+    }
+
+    /// Drops graphic control extensions, leaving every graphic rendering block without one.
+    pub fn discard_graphic_control_extensions(
+        mut self,
+        discard_graphic_control_extensions: bool,
+    ) -> Self {
+        self.discard_graphic_control_extensions = discard_graphic_control_extensions;
+        self
+    }
+
+    /// Drops plain text extensions, along with the graphic control extensions applying to them.
+    pub fn discard_plain_text_extensions(mut self, discard_plain_text_extensions: bool) -> Self {
+        self.discard_plain_text_extensions = discard_plain_text_extensions;
+        self
+    }
+
+    pub fn unknown_extensions(mut self, unknown_extensions: UnknownExtensions) -> Self {
+        self.unknown_extensions = unknown_extensions;
+        self
+    }
+
+    /// Recovers from the most common deviations from the spec, the way browsers do, instead of
+    /// failing: a missing trailer, a truncated last block, a graphic control extension with the
+    /// wrong block size and garbage between blocks. Every deviation is reported as a warning,
+    /// see [`crate::decoder::decode_with_warnings`].
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    pub fn max_logical_screen_size(mut self, width: u16, height: u16) -> Self {
+        self.max_logical_screen_size = Some((width, height));
+        self
+    }
+
+    /// Limits the size of every image, regardless of the logical screen size.
+    pub fn max_frame_size(mut self, width: u16, height: u16) -> Self {
+        self.max_frame_size = Some((width, height));
+        self
+    }
+
+    /// Limits the number of images.
+    pub fn max_frames(mut self, max_frames: usize) -> Self {
+        self.max_frames = Some(max_frames);
+        self
+    }
+
+    /// Limits `image_width * image_height` of every image, which is also the most that the LZW
+    /// decompression of its image data can output.
+    pub fn max_frame_pixels(mut self, max_frame_pixels: u64) -> Self {
+        self.max_frame_pixels = Some(max_frame_pixels);
+        self
+    }
+
+    /// Limits the sum of `image_width * image_height` over all of the images.
+    pub fn max_total_pixels(mut self, max_total_pixels: u64) -> Self {
+        self.max_total_pixels = Some(max_total_pixels);
+        self
+    }
+
+    /// Limits `logical_screen_width * logical_screen_height`, the pixels of the canvas the images
+    /// are composited onto. Rendering with [`crate::GifData::frames_with_options`] allocates the
+    /// canvas up to this limit rather than [`render::MAX_CANVAS_PIXELS`], which can be raised
+    /// this way.
+    pub fn max_canvas_pixels(mut self, max_canvas_pixels: u64) -> Self {
+        self.max_canvas_pixels = Some(max_canvas_pixels);
+        self
+    }
+
+    /// The most pixels a canvas can have when rendering.
+    pub(crate) fn canvas_pixels_limit(&self) -> u64 {
+        self.max_canvas_pixels.unwrap_or(render::MAX_CANVAS_PIXELS)
+    }
+
+    /// Checks the logical screen descriptor found at `offset`.
+    pub(crate) fn check_logical_screen_descriptor(
+        &self,
+        offset: usize,
+        logical_screen_descriptor: &LogicalScreenDescriptor,
+    ) -> Result<(), GifError> {
+        if let Some((width, height)) = self.max_logical_screen_size {
+            check(
+                offset,
+                Limit::LogicalScreenWidth,
+                logical_screen_descriptor.logical_screen_width as u64,
+                width as u64,
+            )?;
+            check(
+                offset,
+                Limit::LogicalScreenHeight,
+                logical_screen_descriptor.logical_screen_height as u64,
+                height as u64,
+            )?;
+        }
+        if let Some(max_canvas_pixels) = self.max_canvas_pixels {
+            check(
+                offset,
+                Limit::CanvasPixels,
+                logical_screen_descriptor.logical_screen_width as u64
+                    * logical_screen_descriptor.logical_screen_height as u64,
+                max_canvas_pixels,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Checks the image descriptor found at `offset`, which is preceded by `frames` images
+    /// totalling `total_pixels` pixels.
+    pub(crate) fn check_image_descriptor(
+        &self,
+        offset: usize,
+        image: &ImageDescriptor,
+        frames: usize,
+        total_pixels: u64,
+    ) -> Result<(), GifError> {
+        if let Some((width, height)) = self.max_frame_size {
+            check(
+                offset,
+                Limit::FrameWidth,
+                image.image_width as u64,
+                width as u64,
+            )?;
+            check(
+                offset,
+                Limit::FrameHeight,
+                image.image_height as u64,
+                height as u64,
+            )?;
+        }
+        if let Some(max_frames) = self.max_frames {
+            check(offset, Limit::Frames, frames as u64 + 1, max_frames as u64)?;
+        }
+        let pixels = image.image_width as u64 * image.image_height as u64;
+        if let Some(max_frame_pixels) = self.max_frame_pixels {
+            check(offset, Limit::FramePixels, pixels, max_frame_pixels)?;
+        }
+        if let Some(max_total_pixels) = self.max_total_pixels {
+            check(
+                offset,
+                Limit::TotalPixels,
+                total_pixels + pixels,
+                max_total_pixels,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn check(offset: usize, limit: Limit, value: u64, max: u64) -> Result<(), GifError> {
+    if value > max {
+        return Err(GifError::LimitExceeded {
+            offset,
+            limit,
+            value,
+            max,
+        });
+    }
+    Ok(())
+}
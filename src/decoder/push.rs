@@ -0,0 +1,358 @@
+//! A decoder that is pushed bytes as they arrive, rather than pulling them from a buffer or a
+//! reader.
+
+use super::{
+    decode_logical_screen_descriptor, decode_signature_and_version, handle_extension_block,
+    skip_garbage, DecodeOptions, Source,
+};
+use crate::{
+    Block, Context, DataSubBlock, ExtensionBlock, GifError, ImageDescriptor,
+    LogicalScreenDescriptor, Span, Version, TRAILER,
+};
+use log::{debug, info};
+use std::borrow::Cow;
+
+/// What a [`PushDecoder`] found in the bytes fed to it.
+#[derive(Debug)]
+pub enum Event<'e> {
+    Header(Version),
+    LogicalScreenDescriptor(LogicalScreenDescriptor<'static>),
+    /// Any block but an image, with the same meaning it has in [`crate::GifData::blocks`].
+    Extension(Block<'static>),
+    /// An image descriptor, with its local color table, graphic control extension and LZW
+    /// minimum code size, but no image data yet.
+    FrameStart(ImageDescriptor<'static>),
+    /// The next sub-block of image data of the current image.
+    FrameData(DataSubBlock<'e>),
+    /// The end of the image data of the current image, with the span of the whole image data.
+    FrameEnd(Span),
+    /// The trailer, anything fed after it is ignored.
+    End,
+    /// A deviation from the spec recovered from when decoding leniently, as the error it would
+    /// have caused otherwise.
+    Warning(GifError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Header,
+    LogicalScreenDescriptor,
+    Blocks,
+    /// Receiving the image data starting at `start`, the offset of the LZW minimum code size.
+    ImageData {
+        start: usize,
+    },
+    End,
+}
+
+/// A decoder for data streams that arrive in arbitrarily sized chunks.
+///
+/// Every chunk is passed to [`PushDecoder::feed`], which reports everything that can be decoded
+/// with the bytes received so far and buffers the rest. Image data is reported one sub-block at
+/// a time, while every other block is only reported once it has been received as a whole.
+pub struct PushDecoder {
+    cx: Context<'static>,
+    /// The bytes received but not decoded yet, starting at `cx.offset`.
+    buffer: Vec<u8>,
+    state: State,
+    error: Option<GifError>,
+}
+
+/// The bytes received so far, at most `offset + buffer.len()` of them.
+struct PushSource<'b> {
+    buffer: &'b [u8],
+    offset: usize,
+}
+
+impl<'b> Source<'b> for PushSource<'b> {
+    fn read(&mut self, offset: usize, len: usize) -> Result<Cow<'b, [u8]>, GifError> {
+        let start = offset - self.offset;
+        let available = self.buffer.len().saturating_sub(start);
+        if available < len {
+            return Err(GifError::UnexpectedEof {
+                offset: offset + available,
+                needed: len - available,
+            });
+        }
+        Ok(Cow::Borrowed(&self.buffer[start..start + len]))
+    }
+
+    fn read_available(&mut self, offset: usize, len: usize) -> Result<Cow<'b, [u8]>, GifError> {
+        let start = (offset - self.offset).min(self.buffer.len());
+        Ok(Cow::Borrowed(
+            &self.buffer[start..(start + len).min(self.buffer.len())],
+        ))
+    }
+}
+
+impl PushDecoder {
+    pub fn new(options: &DecodeOptions) -> Self {
+        Self {
+            cx: Context {
+                incremental: true,
+                ..Context::new(options)
+            },
+            buffer: Vec::new(),
+            state: State::Header,
+            error: None,
+        }
+    }
+
+    /// Whether the trailer has been found.
+    pub fn is_finished(&self) -> bool {
+        self.state == State::End
+    }
+
+    /// Decodes as much as possible of `data`, along with the bytes buffered by the previous
+    /// calls, calling `on_event` for everything found.
+    ///
+    /// An error is final: every later call fails with the same error.
+    pub fn feed(
+        &mut self,
+        data: &[u8],
+        mut on_event: impl FnMut(Event<'_>),
+    ) -> Result<(), GifError> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        if self.state == State::End {
+            if !data.is_empty() {
+                info!("Ignoring {} bytes after the trailer", data.len());
+            }
+            return Ok(());
+        }
+        self.buffer.extend_from_slice(data);
+
+        let start = self.cx.offset;
+        let result = self.decode(&mut on_event);
+        let consumed = self.cx.offset - start;
+        self.buffer.drain(..consumed);
+        if let Err(e) = &result {
+            self.error = Some(e.clone());
+        }
+        result
+    }
+
+    /// Reports that no more data is coming, failing if the trailer hasn't been found.
+    ///
+    /// When decoding leniently a missing trailer is only a warning once the logical screen
+    /// descriptor has been decoded: the current image, if any, ends with the image data received
+    /// so far, including the bytes of a truncated last sub-block. The block being received is kept
+    /// as the other decoders keep it: an extension truncated in its sub-blocks ends with the bytes
+    /// received, one truncated before them is discarded.
+    pub fn finish(mut self, mut on_event: impl FnMut(Event<'_>)) -> Result<(), GifError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.state == State::End {
+            return Ok(());
+        }
+        let end = self.cx.offset + self.buffer.len();
+        // The bytes of the truncated sub-block of the current image, after its size.
+        let partial = match (self.state, self.buffer.split_first()) {
+            (State::ImageData { .. }, Some((&block_size, data))) => Some((block_size, data)),
+            _ => None,
+        };
+        let e = GifError::UnexpectedEof {
+            offset: end,
+            needed: partial.map_or(1, |(block_size, data)| block_size as usize - data.len()),
+        };
+        if !self.cx.options.lenient
+            || matches!(self.state, State::Header | State::LogicalScreenDescriptor)
+        {
+            return Err(e);
+        }
+        if let State::ImageData { start } = self.state {
+            on_event(Event::Warning(e));
+            if let Some((_, data)) = partial.filter(|(_, data)| !data.is_empty()) {
+                on_event(Event::FrameData(DataSubBlock {
+                    block_size: data.len() as u8,
+                    data: Cow::Borrowed(data),
+                    span: Some(Span {
+                        start: self.cx.offset,
+                        end,
+                    }),
+                }));
+            }
+            on_event(Event::FrameEnd(Span { start, end }));
+        } else {
+            // No more bytes are coming, so the block is decoded the way the slice decoder would,
+            // recording the warning for the bytes missing.
+            self.cx.incremental = false;
+            if !self.decode_block(self.cx.offset, &mut on_event)? {
+                on_event(Event::Warning(e));
+            }
+            for warning in self.cx.warnings.drain(..) {
+                on_event(Event::Warning(warning));
+            }
+        }
+        if let Some(ext) = self.cx.graphic_control_extension.take() {
+            on_event(Event::Extension(Block::GraphicControl(ext)));
+        }
+        on_event(Event::End);
+        Ok(())
+    }
+
+    fn decode(&mut self, on_event: &mut impl FnMut(Event<'_>)) -> Result<(), GifError> {
+        let start = self.cx.offset;
+        loop {
+            for warning in self.cx.warnings.drain(..) {
+                on_event(Event::Warning(warning));
+            }
+            match self.state {
+                State::Header => {
+                    let version = match self
+                        .try_decode(start, |cx, bytes| decode_signature_and_version(cx, bytes))?
+                    {
+                        Some(version) => version,
+                        None => return Ok(()),
+                    };
+                    on_event(Event::Header(version));
+                    self.state = State::LogicalScreenDescriptor;
+                }
+                State::LogicalScreenDescriptor => {
+                    let logical_screen_descriptor = match self.try_decode(start, |cx, bytes| {
+                        Ok(decode_logical_screen_descriptor(cx, bytes)?.into_owned())
+                    })? {
+                        Some(logical_screen_descriptor) => logical_screen_descriptor,
+                        None => return Ok(()),
+                    };
+                    on_event(Event::LogicalScreenDescriptor(logical_screen_descriptor));
+                    self.state = State::Blocks;
+                }
+                State::Blocks => {
+                    if !self.decode_block(start, on_event)? {
+                        return Ok(());
+                    }
+                }
+                State::ImageData {
+                    start: image_data_start,
+                } => {
+                    // The span of the sub-block, `None` for the block terminator.
+                    let span = match self.try_decode(start, |cx, bytes| {
+                        Ok(DataSubBlock::decode(cx, bytes)?.and_then(|block| block.span))
+                    })? {
+                        Some(span) => span,
+                        None => return Ok(()),
+                    };
+                    match span {
+                        Some(span) => on_event(Event::FrameData(DataSubBlock {
+                            block_size: (span.len() - 1) as u8,
+                            data: Cow::Borrowed(
+                                &self.buffer[span.start + 1 - start..span.end - start],
+                            ),
+                            span: Some(span),
+                        })),
+                        None => {
+                            on_event(Event::FrameEnd(Span {
+                                start: image_data_start,
+                                end: self.cx.offset,
+                            }));
+                            self.state = State::Blocks;
+                        }
+                    }
+                }
+                State::End => return Ok(()),
+            }
+        }
+    }
+
+    /// Decodes the next block, returning whether there were enough bytes for it.
+    fn decode_block(
+        &mut self,
+        start: usize,
+        on_event: &mut impl FnMut(Event<'_>),
+    ) -> Result<bool, GifError> {
+        let decoded = self.try_decode(start, |cx, bytes| {
+            let byte = match cx.read_u8(bytes)? {
+                byte @ (ExtensionBlock::INTRODUCER | ImageDescriptor::SEPARATOR | TRAILER) => byte,
+                byte => skip_garbage(cx, bytes, byte)?,
+            };
+            Ok(match byte {
+                ExtensionBlock::INTRODUCER => Unit::Extension(
+                    ExtensionBlock::decode(cx, bytes)?.map(ExtensionBlock::into_owned),
+                ),
+                ImageDescriptor::SEPARATOR => {
+                    let offset = cx.offset - 1;
+                    let mut image = ImageDescriptor::decode_without_image_data(cx, bytes)?;
+                    image.image_data.lzw_minimum_code_size = cx.read_u8(bytes)?;
+                    Unit::Image(offset, image.into_owned())
+                }
+                // The trailer, as `skip_garbage` only returns bytes that introduce a block.
+                _ => Unit::Trailer,
+            })
+        })?;
+
+        match decoded {
+            None => return Ok(false),
+            Some(Unit::Extension(None)) => {}
+            Some(Unit::Extension(Some(extension_block))) => {
+                let extension_block_s: &'static str = (&extension_block).into();
+                info!("Found {} extension block", extension_block_s);
+                debug!("[{:?}] Extension block: {:?}", self.cx, extension_block);
+                let block = handle_extension_block(&mut self.cx, extension_block);
+                if let Some(block) = block {
+                    on_event(Event::Extension(block));
+                }
+                if let Some(block) = self.cx.pending_block.take() {
+                    on_event(Event::Extension(block));
+                }
+            }
+            Some(Unit::Image(offset, mut image)) => {
+                self.cx.check_image_descriptor(offset, &image)?;
+                info!("Found image descriptor");
+                image.graphic_control_extension = self.cx.graphic_control_extension.take();
+                debug!("[{:?}] Image descriptor: {:?}", self.cx, image);
+                on_event(Event::FrameStart(image));
+                self.state = State::ImageData {
+                    start: self.cx.offset - 1,
+                };
+            }
+            Some(Unit::Trailer) => {
+                info!("End of GIF data stream");
+                if let Some(ext) = self.cx.graphic_control_extension.take() {
+                    info!("Keeping a graphic control extension followed by the trailer");
+                    on_event(Event::Extension(Block::GraphicControl(ext)));
+                }
+                on_event(Event::End);
+                self.state = State::End;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs `decode` over the buffered bytes, returning `None` and rewinding if they aren't
+    /// enough. `start` is the offset of the first buffered byte.
+    ///
+    /// The complete sub-blocks of the block being received are kept in the context though, so a
+    /// long extension costs the same whatever the size of the chunks it arrives in.
+    fn try_decode<T>(
+        &mut self,
+        start: usize,
+        decode: impl FnOnce(&mut Context<'static>, &mut PushSource<'_>) -> Result<T, GifError>,
+    ) -> Result<Option<T>, GifError> {
+        let offset = self.cx.offset;
+        let warnings = self.cx.warnings.len();
+        let mut source = PushSource {
+            buffer: &self.buffer,
+            offset: start,
+        };
+        match decode(&mut self.cx, &mut source) {
+            Ok(decoded) => Ok(Some(decoded)),
+            Err(GifError::UnexpectedEof { .. }) => {
+                self.cx.offset = offset;
+                self.cx.warnings.truncate(warnings);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// A unit of the data stream the push decoder decodes at once.
+enum Unit {
+    Extension(Option<ExtensionBlock<'static>>),
+    /// An image descriptor and the offset of its separator.
+    Image(usize, ImageDescriptor<'static>),
+    Trailer,
+}
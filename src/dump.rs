@@ -0,0 +1,447 @@
+//! An annotated tree of every block of a decoded GIF, as text for people or as JSON for tools.
+//!
+//! Each block comes with the span it was decoded from, its packed fields decoded, and for image
+//! data the sizes of the sub-blocks and how well the LZW compression did.
+
+use crate::{
+    lzw, ApplicationExtension, Block, ColorTable, DataSubBlocks, DisposalMethod, GifData,
+    GraphicControlExtension, GraphicRenderingBlock, ImageDescriptor, PlainTextExtension, Span,
+    TableBasedImageData, Version,
+};
+use std::fmt::Write;
+
+/// Colors per line in the text dump.
+const COLORS_PER_LINE: usize = 8;
+
+/// The tree as indented text. Color tables are listed as `#rrggbb` colors, each one preceded by a
+/// swatch drawn with ANSI true color escape codes if `swatches` is set.
+pub fn text(gif: &GifData, swatches: bool) -> String {
+    let mut out = String::new();
+    for node in tree(gif) {
+        node.write_text(&mut out, 0, swatches);
+    }
+    out
+}
+
+/// The tree as a JSON object with a `blocks` array. Each block has a `kind`, its `start` and
+/// `end` offsets (`null` if it wasn't decoded), its `fields` and its `children`.
+pub fn json(gif: &GifData) -> String {
+    let mut out = String::from("{\"blocks\":[");
+    for (i, node) in tree(gif).iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        node.write_json(&mut out);
+    }
+    out.push_str("]}");
+    out
+}
+
+struct Node {
+    kind: &'static str,
+    span: Option<Span>,
+    fields: Vec<(&'static str, Value)>,
+    children: Vec<Node>,
+}
+
+enum Value {
+    Bool(bool),
+    Int(u64),
+    Float(f64),
+    /// A name chosen by this module, printed as it is.
+    Name(&'static str),
+    /// Text found in the GIF, quoted and escaped.
+    Text(String),
+    Ints(Vec<u64>),
+    Colors(Vec<[u8; 3]>),
+}
+
+impl Node {
+    fn new(kind: &'static str, span: Option<Span>) -> Self {
+        Self {
+            kind,
+            span,
+            fields: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn field(mut self, name: &'static str, value: Value) -> Self {
+        self.fields.push((name, value));
+        self
+    }
+
+    fn int(self, name: &'static str, value: impl Into<u64>) -> Self {
+        self.field(name, Value::Int(value.into()))
+    }
+
+    fn child(mut self, child: Option<Node>) -> Self {
+        self.children.extend(child);
+        self
+    }
+
+    fn write_text(&self, out: &mut String, depth: usize, swatches: bool) {
+        let indent = "    ".repeat(depth);
+        out.push_str(&indent);
+        out.push_str(self.kind);
+        if let Some(span) = self.span {
+            let _ = write!(out, " at {}..{}", span.start, span.end);
+        }
+        out.push('\n');
+        for (name, value) in &self.fields {
+            let _ = write!(out, "{}    {}: ", indent, name);
+            match value {
+                Value::Bool(value) => {
+                    let _ = write!(out, "{}", value);
+                }
+                Value::Int(value) => {
+                    let _ = write!(out, "{}", value);
+                }
+                Value::Float(value) => {
+                    let _ = write!(out, "{:.3}", value);
+                }
+                Value::Name(value) => out.push_str(value),
+                Value::Text(value) => {
+                    let _ = write!(out, "{:?}", value);
+                }
+                Value::Ints(values) => out.push_str(&run_lengths(values)),
+                Value::Colors(colors) => {
+                    let _ = write!(out, "{}", colors.len());
+                    for (line, colors) in colors.chunks(COLORS_PER_LINE).enumerate() {
+                        let _ = write!(out, "\n{}        {:>3}:", indent, line * COLORS_PER_LINE);
+                        for &[r, g, b] in colors {
+                            out.push(' ');
+                            if swatches {
+                                let _ = write!(out, "\x1b[48;2;{};{};{}m  \x1b[0m ", r, g, b);
+                            }
+                            let _ = write!(out, "#{:02x}{:02x}{:02x}", r, g, b);
+                        }
+                    }
+                }
+            }
+            out.push('\n');
+        }
+        for child in &self.children {
+            child.write_text(out, depth + 1, swatches);
+        }
+    }
+
+    fn write_json(&self, out: &mut String) {
+        out.push_str("{\"kind\":");
+        write_json_string(out, self.kind);
+        match self.span {
+            Some(span) => {
+                let _ = write!(out, ",\"start\":{},\"end\":{}", span.start, span.end);
+            }
+            None => out.push_str(",\"start\":null,\"end\":null"),
+        }
+        out.push_str(",\"fields\":{");
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(out, name);
+            out.push(':');
+            match value {
+                Value::Bool(value) => {
+                    let _ = write!(out, "{}", value);
+                }
+                Value::Int(value) => {
+                    let _ = write!(out, "{}", value);
+                }
+                Value::Float(value) if value.is_finite() => {
+                    let _ = write!(out, "{}", value);
+                }
+                Value::Float(_) => out.push_str("null"),
+                Value::Name(value) => write_json_string(out, value),
+                Value::Text(value) => write_json_string(out, value),
+                Value::Ints(values) => {
+                    let values: Vec<String> = values.iter().map(u64::to_string).collect();
+                    let _ = write!(out, "[{}]", values.join(","));
+                }
+                Value::Colors(colors) => {
+                    let colors: Vec<String> = colors
+                        .iter()
+                        .map(|[r, g, b]| format!("\"#{:02x}{:02x}{:02x}\"", r, g, b))
+                        .collect();
+                    let _ = write!(out, "[{}]", colors.join(","));
+                }
+            }
+        }
+        out.push_str("},\"children\":[");
+        for (i, child) in self.children.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            child.write_json(out);
+        }
+        out.push_str("]}");
+    }
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// `values` with runs of the same value collapsed, as in `255 x 3, 12`.
+fn run_lengths(values: &[u64]) -> String {
+    let mut runs: Vec<(u64, usize)> = Vec::new();
+    for &value in values {
+        match runs.last_mut() {
+            Some((last, count)) if *last == value => *count += 1,
+            _ => runs.push((value, 1)),
+        }
+    }
+    if runs.is_empty() {
+        return "none".to_string();
+    }
+    let runs: Vec<String> = runs
+        .into_iter()
+        .map(|(value, count)| match count {
+            1 => value.to_string(),
+            count => format!("{} x {}", value, count),
+        })
+        .collect();
+    runs.join(", ")
+}
+
+fn tree(gif: &GifData) -> Vec<Node> {
+    let lsd = &gif.logical_screen_descriptor;
+    // The header is only known to be there if the rest was decoded.
+    let header = Node::new(
+        "header",
+        lsd.span.map(|_| Span {
+            start: 0,
+            end: crate::SIGNATURE.len() + 3,
+        }),
+    )
+    .field(
+        "version",
+        Value::Name(match gif.version {
+            Version::V87a => "87a",
+            Version::V89a => "89a",
+        }),
+    );
+    let mut node = Node::new("logical screen descriptor", lsd.span)
+        .int("logical_screen_width", lsd.logical_screen_width)
+        .int("logical_screen_height", lsd.logical_screen_height)
+        .int("global_color_table_flag", lsd.global_color_table_flag())
+        .int("color_resolution", lsd.color_resolution())
+        .int("sort_flag", lsd.sort_flag())
+        .int("global_color_table_size", lsd.global_color_table_size())
+        .int("background_color_index", lsd.background_color_index)
+        .int("pixel_aspect_ratio", lsd.pixel_aspect_ratio);
+    if lsd.pixel_aspect_ratio != 0 {
+        node = node.field("aspect_ratio", Value::Float(lsd.aspect_ratio() as f64));
+    }
+    let lsd = node.child(
+        lsd.global_color_table
+            .as_ref()
+            .map(|table| color_table("global color table", table)),
+    );
+
+    let mut nodes = vec![header, lsd];
+    for block in &gif.blocks {
+        nodes.push(match block {
+            Block::Application(ext) => application_extension(ext),
+            Block::Comment(ext) => Node::new("comment extension", ext.span)
+                .field("text", text_value(&ext.data))
+                .field("sub_block_sizes", sub_block_sizes(&ext.data)),
+            Block::GraphicControl(ext) => graphic_control_extension(ext),
+            Block::Unknown { label, data, span } => Node::new("unknown extension", *span)
+                .int("label", *label)
+                .field("sub_block_sizes", sub_block_sizes(data)),
+            Block::GraphicRendering(GraphicRenderingBlock::PlainText(ext)) => {
+                plain_text_extension(ext)
+            }
+            Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => image_descriptor(image),
+        });
+    }
+    // A decoded document without a trailer comes from a data stream missing it, while an edited
+    // one gets it once encoded.
+    if gif.trailer.is_some() || gif.logical_screen_descriptor.span.is_none() {
+        nodes.push(Node::new("trailer", gif.trailer));
+    }
+    nodes
+}
+
+fn color_table(kind: &'static str, table: &ColorTable) -> Node {
+    let colors = table
+        .pixels()
+        .chunks(3)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+        .collect();
+    Node::new(kind, table.span).field("colors", Value::Colors(colors))
+}
+
+fn sub_block_sizes(data: &DataSubBlocks) -> Value {
+    Value::Ints(
+        data.blocks
+            .iter()
+            .map(|block| block.data.len() as u64)
+            .collect(),
+    )
+}
+
+fn text_value(data: &DataSubBlocks) -> Value {
+    let bytes: Vec<u8> = data
+        .blocks
+        .iter()
+        .flat_map(|block| block.data.iter().copied())
+        .collect();
+    Value::Text(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn application_extension(ext: &ApplicationExtension) -> Node {
+    let mut node = Node::new("application extension", ext.span)
+        .field(
+            "identifier",
+            Value::Text(String::from_utf8_lossy(&ext.identifier).into_owned()),
+        )
+        .field(
+            "authentication_code",
+            Value::Text(String::from_utf8_lossy(&ext.authentication_code).into_owned()),
+        );
+    if let Some(netscape) = ext.netscape() {
+        if let Some(loop_count) = netscape.loop_count {
+            node = node.int("loop_count", loop_count);
+        }
+        if let Some(buffering_size) = netscape.buffering_size {
+            node = node.int("buffering_size", buffering_size);
+        }
+    }
+    node.field("sub_block_sizes", sub_block_sizes(&ext.data))
+}
+
+fn graphic_control_extension(ext: &GraphicControlExtension) -> Node {
+    let disposal = match ext.disposal_method() {
+        DisposalMethod::Unspecified => "unspecified",
+        DisposalMethod::None => "none",
+        DisposalMethod::RestoreBackground => "restore background",
+        DisposalMethod::RestorePrevious => "restore previous",
+        DisposalMethod::Reserved(_) => "reserved",
+    };
+    Node::new("graphic control extension", ext.span)
+        .int("disposal_method", u8::from(ext.disposal_method()))
+        .field("disposal", Value::Name(disposal))
+        .int("user_input_flag", ext.user_input_flag())
+        .int("transparent_color_flag", ext.transparent_color_flag())
+        .int("delay_time", ext.delay_time)
+        .int("transparent_color_index", ext.transparent_color_index)
+}
+
+fn plain_text_extension(ext: &PlainTextExtension) -> Node {
+    Node::new("plain text extension", ext.span)
+        .int("text_grid_left_position", ext.text_grid_left_position)
+        .int("text_grid_top_position", ext.text_grid_top_position)
+        .int("text_grid_width", ext.text_grid_width)
+        .int("text_grid_height", ext.text_grid_height)
+        .int("character_cell_width", ext.character_cell_width)
+        .int("character_cell_height", ext.character_cell_height)
+        .int(
+            "text_foreground_color_index",
+            ext.text_foreground_color_index,
+        )
+        .int(
+            "text_background_color_index",
+            ext.text_background_color_index,
+        )
+        .field("text", text_value(&ext.data))
+        .field("sub_block_sizes", sub_block_sizes(&ext.data))
+        .child(
+            ext.graphic_control_extension
+                .as_ref()
+                .map(graphic_control_extension),
+        )
+}
+
+fn image_descriptor(image: &ImageDescriptor) -> Node {
+    Node::new("image descriptor", image.span)
+        .int("image_left_position", image.image_left_position)
+        .int("image_top_position", image.image_top_position)
+        .int("image_width", image.image_width)
+        .int("image_height", image.image_height)
+        .int("local_color_table_flag", image.local_color_table_flag())
+        .int("interlace_flag", image.interlace_flag())
+        .int("sort_flag", image.sort_flag())
+        .int("local_color_table_size", image.local_color_table_size())
+        .child(
+            image
+                .graphic_control_extension
+                .as_ref()
+                .map(graphic_control_extension),
+        )
+        .child(
+            image
+                .local_color_table
+                .as_ref()
+                .map(|table| color_table("local color table", table)),
+        )
+        .child(Some(image_data(
+            &image.image_data,
+            image.image_width as usize * image.image_height as usize,
+        )))
+}
+
+/// The image data and the outcome of decompressing its `pixels` color indices.
+fn image_data(data: &TableBasedImageData, pixels: usize) -> Node {
+    let compressed: usize = data
+        .image_data
+        .blocks
+        .iter()
+        .map(|block| block.data.len())
+        .sum();
+    let mut node = Node::new("image data", data.span)
+        .int("lzw_minimum_code_size", data.lzw_minimum_code_size)
+        .field("sub_block_sizes", sub_block_sizes(&data.image_data))
+        .int("compressed_bytes", compressed as u64)
+        .int("pixels", pixels as u64);
+    if pixels > 0 {
+        node = node.field(
+            "bits_per_pixel",
+            Value::Float(compressed as f64 * 8.0 / pixels as f64),
+        );
+    }
+
+    let mut decoder = match lzw::Decoder::new(data.lzw_minimum_code_size) {
+        Ok(decoder) => decoder,
+        Err(e) => return node.field("error", Value::Text(e.to_string())),
+    };
+    let mut indices = Vec::new();
+    let mut error = None;
+    for block in &data.image_data.blocks {
+        if let Err(e) = decoder.decode_bytes(&block.data, &mut indices, pixels) {
+            error = Some(e);
+            break;
+        }
+        if decoder.is_finished() {
+            break;
+        }
+    }
+    let stats = decoder.stats();
+    node = node
+        .int("decoded_pixels", indices.len() as u64)
+        .int("codes", stats.codes as u64)
+        .int("clear_codes", stats.clear_codes as u64)
+        .int("max_code_size", stats.max_code_size)
+        .field("end_of_information", Value::Bool(decoder.is_finished()))
+        .int("unused_bytes", (compressed - decoder.consumed()) as u64);
+    if let Some(e) = error {
+        node = node.field("error", Value::Text(e.to_string()));
+    }
+    node
+}
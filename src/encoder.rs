@@ -1,230 +1,406 @@
 use crate::{
-    ApplicationExtension, CommentExtension, DataSubBlock, DataSubBlocks, ExtensionBlock, GifData,
-    GraphicControlExtension, GraphicRenderingBlock, ImageDescriptor, LogicalScreenDescriptor,
-    PlainTextExtension, TableBasedImageData, Version, TRAILER,
+    interlace, ApplicationExtension, Block, ColorTable, CommentExtension, DataSubBlock,
+    DataSubBlocks, ExtensionBlock, GifData, GifError, GraphicControlExtension,
+    GraphicRenderingBlock, ImageDescriptor, LogicalScreenDescriptor, PlainTextExtension,
+    TableBasedImageData, Version, SIGNATURE, TRAILER,
 };
-use log::{debug, info};
+use log::{debug, info, warn};
+use std::io::{self, Write};
 
 impl<'a> GifData<'a> {
-    pub fn encode(&self, version: &Version, discard_comments: bool) -> Vec<u8> {
-        let mut data = Vec::new();
+    /// Fails if a block is inconsistent, see [`Encoder`]. Encoding to GIF87a drops the blocks it
+    /// doesn't define, see [`GifData::convert`] for a conversion that mitigates and reports that.
+    pub fn encode(&self, version: &Version, discard_comments: bool) -> Result<Vec<u8>, GifError> {
+        let mut encoder = Encoder::new(
+            Vec::new(),
+            *version,
+            &self.logical_screen_descriptor,
+            discard_comments,
+        )?;
+        for block in &self.blocks {
+            encoder.write_block(block)?;
+        }
+        encoder.finish()
+    }
+}
+
+/// Encodes a GIF data stream straight into a writer, one block at a time.
+///
+/// The header is written on creation, then any number of blocks can be pushed, and
+/// [`Encoder::finish`] writes the trailer. Blocks are written with many small writes, so
+/// unbuffered writers should be wrapped in a [`std::io::BufWriter`].
+///
+/// Writing fails on blocks whose fields contradict each other, leaving the output incomplete: a
+/// color table that doesn't match the flag and size in the packed fields
+/// ([`GifError::ColorTableMismatch`]), a sub-block whose size doesn't match its data
+/// ([`GifError::SubBlockSizeMismatch`]) and an application extension whose identifier and
+/// authentication code aren't 11 bytes long ([`GifError::BadBlockSize`]). The offsets of the
+/// errors are where the inconsistent blocks would have started in the output.
+pub struct Encoder<W: Write> {
+    writer: Output<W>,
+    version: Version,
+    discard_comments: bool,
+    interlace: bool,
+}
 
-        data.extend_from_slice(b"GIF");
+impl<W: Write> Encoder<W> {
+    /// Writes the signature, the version and the logical screen descriptor.
+    pub fn new(
+        writer: W,
+        version: Version,
+        logical_screen_descriptor: &LogicalScreenDescriptor,
+        discard_comments: bool,
+    ) -> Result<Self, GifError> {
+        let mut writer = Output { writer, offset: 0 };
+        writer.write_all(SIGNATURE)?;
         info!("Wrote the GIF signature");
-        data.extend_from_slice(version.encode());
+        version.write(&mut writer)?;
         info!("Wrote the GIF version: {:?}", version);
-        let logical_screen_descriptor = &self.logical_screen_descriptor.encode();
-        data.extend_from_slice(logical_screen_descriptor);
+        logical_screen_descriptor.write(&mut writer)?;
         info!("Wrote the logical screen descriptor");
-        debug!(
-            "Logical screen descriptor: {:x?}",
-            logical_screen_descriptor
-        );
+        debug!("Logical screen descriptor: {:?}", logical_screen_descriptor);
+        Ok(Self {
+            writer,
+            version,
+            discard_comments,
+            interlace: false,
+        })
+    }
 
-        if *version == Version::V87a {
-            info!("Discarding {} application extensions because they're not supported in the target version '{:?}'", self.application_extensions.len(), version);
-        } else {
-            for ext in &self.application_extensions {
-                let ext = &ext.encode();
-                data.extend_from_slice(ext);
-                info!("Wrote an application extension");
-                debug!("Application extension: {:x?}", ext);
+    /// Whether images that aren't interlaced should be interlaced while being written, so that
+    /// viewers can display them progressively. Their image data has to be decompressed and
+    /// compressed again.
+    pub fn set_interlace(&mut self, interlace: bool) {
+        self.interlace = interlace;
+    }
+
+    /// Writes a block, unless it's not supported in the target version or it's a comment and
+    /// comments are being discarded.
+    pub fn write_block(&mut self, block: &Block) -> Result<(), GifError> {
+        let version = self.version;
+        match block {
+            Block::Application(ext) => {
+                if version == Version::V87a {
+                    warn!("Discarding application extension because it's not supported in the target version '{:?}'", version);
+                } else {
+                    ext.write(&mut self.writer)?;
+                    info!("Wrote an application extension");
+                    debug!("Application extension: {:?}", ext);
+                }
             }
-        }
-        if !discard_comments && *version != Version::V87a {
-            for ext in &self.comment_extensions {
-                let ext = &ext.encode();
-                data.extend_from_slice(ext);
-                info!("Wrote a comment extension");
-                debug!("Comment extension: {:x?}", ext);
+            Block::Comment(ext) => {
+                if version == Version::V87a {
+                    warn!("Discarding comment extension because it's not supported in the target version '{:?}'", version);
+                } else if self.discard_comments {
+                    info!("Discarding comment extension");
+                } else {
+                    ext.write(&mut self.writer)?;
+                    info!("Wrote a comment extension");
+                    debug!("Comment extension: {:?}", ext);
+                }
             }
-        } else if *version == Version::V87a {
-            info!("Discarding {} comment extensions because they're not supported in the target version '{:?}'", self.comment_extensions.len(), version);
-        } else {
-            info!(
-                "Discarding {} comment extensions",
-                self.comment_extensions.len()
-            );
-        }
-        for block in &self.graphic_rendering_blocks {
-            match block {
-                GraphicRenderingBlock::Image(_) => {
-                    let block_data = &block.encode(version);
-                    data.extend_from_slice(block_data);
-                    info!("Wrote an image descriptor");
-                    debug!("Image descriptor: {:x?}", block_data);
+            Block::GraphicControl(ext) => {
+                if version == Version::V87a {
+                    warn!("Discarding graphic control extension because it's not supported in the target version '{:?}'", version);
+                } else {
+                    ext.write(&mut self.writer)?;
+                    info!("Wrote a graphic control extension");
+                    debug!("Graphic control extension: {:?}", ext);
                 }
-                GraphicRenderingBlock::PlainText(_) => {
-                    if *version == Version::V87a {
-                        info!("Discarding plain text extension because it's not supported in the target version '{:?}'", version);
-                    } else {
-                        let block_data = &block.encode(version);
-                        data.extend_from_slice(block_data);
-                        info!("Wrote a plain text extension");
-                        debug!("Plain text extension: {:x?}", block_data);
-                    }
+            }
+            Block::Unknown { label, data, .. } => {
+                data.check(self.writer.offset + 2)?;
+                self.writer
+                    .write_all(&[ExtensionBlock::INTRODUCER, *label])?;
+                data.write(&mut self.writer)?;
+                info!("Wrote an unknown extension with label '0x{:x}'", label);
+                debug!("Unknown extension: {:?}", data);
+            }
+            Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+                self.write_frame(image)?;
+            }
+            Block::GraphicRendering(GraphicRenderingBlock::PlainText(ext)) => {
+                if version == Version::V87a {
+                    warn!("Discarding plain text extension because it's not supported in the target version '{:?}'", version);
+                } else {
+                    ext.write(&mut self.writer)?;
+                    info!("Wrote a plain text extension");
+                    debug!("Plain text extension: {:?}", ext);
                 }
             }
         }
-        data.push(TRAILER);
+        Ok(())
+    }
+
+    /// Writes an image, preceded by its graphic control extension if the target version
+    /// supports it.
+    ///
+    /// Fails with the decompression error if the image has to be interlaced but its image data
+    /// can't be decompressed.
+    pub fn write_frame(&mut self, image: &ImageDescriptor) -> Result<(), GifError> {
+        if self.interlace && image.interlace_flag() == 0 {
+            let indices = image.indices()?;
+            let indices = interlace::interlace(&indices, image.image_width as usize);
+            let mut image = image.clone();
+            image.image_data =
+                TableBasedImageData::compress(&indices, image.image_data.lzw_minimum_code_size)?;
+            image.set_interlace_flag(true);
+            info!("Interlaced an image");
+            image.write(&mut self.writer, &self.version)?;
+        } else {
+            image.write(&mut self.writer, &self.version)?;
+        }
+        info!("Wrote an image descriptor");
+        debug!("Image descriptor: {:?}", image);
+        Ok(())
+    }
+
+    /// Writes the trailer and gives the writer back.
+    pub fn finish(mut self) -> Result<W, GifError> {
+        self.writer.write_all(&[TRAILER])?;
         info!("Wrote the GIF trailer");
+        let offset = self.writer.offset;
+        self.writer
+            .writer
+            .flush()
+            .map_err(|e| io_error(offset, e))?;
+        Ok(self.writer.writer)
+    }
+}
+
+/// A writer keeping track of how many bytes have been written to it, for the offsets of the
+/// errors.
+struct Output<W> {
+    writer: W,
+    offset: usize,
+}
+
+impl<W: Write> Output<W> {
+    fn write_all(&mut self, data: &[u8]) -> Result<(), GifError> {
+        self.writer
+            .write_all(data)
+            .map_err(|e| io_error(self.offset, e))?;
+        self.offset += data.len();
+        Ok(())
+    }
+}
+
+fn io_error(offset: usize, e: io::Error) -> GifError {
+    GifError::Io {
+        offset,
+        kind: e.kind(),
+        message: e.to_string(),
+    }
+}
 
-        data
+/// Checks that a color table matches the flag and size describing it in the packed fields of
+/// the block about to be written at `offset`.
+fn check_color_table(
+    offset: usize,
+    flag: u8,
+    size_bits: u8,
+    color_table: Option<&ColorTable>,
+) -> Result<(), GifError> {
+    let expected = crate::color_table_len(flag, size_bits);
+    let found = color_table.map(ColorTable::len);
+    if expected != found {
+        return Err(GifError::ColorTableMismatch {
+            offset,
+            expected,
+            found,
+        });
     }
+    Ok(())
 }
 
 impl Version {
-    fn encode(&self) -> &'static [u8] {
-        match self {
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
+        w.write_all(match self {
             Self::V87a => b"87a",
             Self::V89a => b"89a",
-        }
+        })
     }
 }
 
 impl<'a> LogicalScreenDescriptor<'a> {
-    fn encode(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        data.push((self.logical_screen_width >> 8) as u8);
-        data.push(((self.logical_screen_width << 8) >> 8) as u8);
-        data.push((self.logical_screen_height >> 8) as u8);
-        data.push(((self.logical_screen_height << 8) >> 8) as u8);
-        data.push(self.packed_fields);
-        data.push(self.background_color_index);
-        data.push(self.pixel_aspect_ratio);
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
+        check_color_table(
+            w.offset,
+            self.global_color_table_flag(),
+            self.global_color_table_size(),
+            self.global_color_table.as_ref(),
+        )?;
+        w.write_all(&self.logical_screen_width.to_le_bytes())?;
+        w.write_all(&self.logical_screen_height.to_le_bytes())?;
+        w.write_all(&[
+            self.packed_fields,
+            self.background_color_index,
+            self.pixel_aspect_ratio,
+        ])?;
         if let Some(global_color_table) = &self.global_color_table {
-            data.extend_from_slice(global_color_table.pixels);
+            w.write_all(&global_color_table.pixels)?;
         }
-        data
+        Ok(())
     }
 }
 
-impl<'a> GraphicRenderingBlock<'a> {
-    fn encode(&self, version: &Version) -> Vec<u8> {
-        match self {
-            Self::PlainText(block) => block.encode(),
-            Self::Image(block) => block.encode(version),
+impl<'a> DataSubBlock<'a> {
+    /// Checks the sub-block about to be written at `offset`.
+    fn check(&self, offset: usize) -> Result<(), GifError> {
+        if self.block_size as usize != self.data.len() {
+            return Err(GifError::SubBlockSizeMismatch {
+                offset,
+                block_size: self.block_size,
+                len: self.data.len(),
+            });
         }
+        if self.data.is_empty() {
+            return Err(GifError::EmptySubBlock { offset });
+        }
+        Ok(())
     }
-}
 
-impl<'a> DataSubBlock<'a> {
-    fn encode(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        data.push(self.block_size);
-        data.extend_from_slice(self.data);
-        data
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
+        w.write_all(&[self.block_size])?;
+        w.write_all(&self.data)
     }
 }
 
 impl<'a> DataSubBlocks<'a> {
-    fn encode(&self) -> Vec<u8> {
-        let mut data = Vec::new();
+    /// Checks every sub-block, the first one being about to be written at `offset`. The blocks
+    /// holding sub-blocks call this before writing any of their bytes, so that a rejected block
+    /// isn't partially written.
+    fn check(&self, mut offset: usize) -> Result<(), GifError> {
+        for block in &self.blocks {
+            block.check(offset)?;
+            offset += 1 + block.data.len();
+        }
+        Ok(())
+    }
+
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
         for block in &self.blocks {
-            data.extend_from_slice(&block.encode());
+            block.write(w)?;
         }
-        data.push(DataSubBlock::BLOCK_TERMINATOR);
-        data
+        w.write_all(&[DataSubBlock::BLOCK_TERMINATOR])
     }
 }
 
 impl<'a> ApplicationExtension<'a> {
-    fn encode(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        data.push(ExtensionBlock::INTRODUCER);
-        data.push(Self::LABEL);
-        data.push(Self::BLOCK_SIZE);
-        data.extend_from_slice(self.identifier);
-        data.extend_from_slice(self.authentication_code);
-        data.extend_from_slice(&self.data.encode());
-        data
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
+        let len = self.identifier.len() + self.authentication_code.len();
+        if self.identifier.len() != 8 || self.authentication_code.len() != 3 {
+            return Err(GifError::BadBlockSize {
+                offset: w.offset,
+                expected: Self::BLOCK_SIZE,
+                found: len.min(u8::MAX as usize) as u8,
+            });
+        }
+        self.data.check(w.offset + 3 + Self::BLOCK_SIZE as usize)?;
+        w.write_all(&[ExtensionBlock::INTRODUCER, Self::LABEL, Self::BLOCK_SIZE])?;
+        w.write_all(&self.identifier)?;
+        w.write_all(&self.authentication_code)?;
+        self.data.write(w)
     }
 }
 
 impl<'a> PlainTextExtension<'a> {
-    fn encode(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        data.push(ExtensionBlock::INTRODUCER);
-        data.push(Self::LABEL);
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
+        let graphic_control_extension_len = self
+            .graphic_control_extension
+            .as_ref()
+            .map_or(0, |_| GraphicControlExtension::LEN);
+        self.data
+            .check(w.offset + graphic_control_extension_len + 3 + Self::BLOCK_SIZE as usize)?;
         if let Some(graphic_control_extension) = &self.graphic_control_extension {
-            data.extend_from_slice(&graphic_control_extension.encode());
+            graphic_control_extension.write(w)?;
         }
-        data.push(Self::BLOCK_SIZE);
-        data.push((self.text_grid_left_position >> 8) as u8);
-        data.push(((self.text_grid_left_position << 8) >> 8) as u8);
-        data.push((self.text_grid_top_position >> 8) as u8);
-        data.push(((self.text_grid_top_position << 8) >> 8) as u8);
-        data.push((self.text_grid_width >> 8) as u8);
-        data.push(((self.text_grid_width << 8) >> 8) as u8);
-        data.push((self.text_grid_height >> 8) as u8);
-        data.push(((self.text_grid_height << 8) >> 8) as u8);
-        data.push(self.character_cell_width);
-        data.push(self.character_cell_height);
-        data.push(self.text_foreground_color_index);
-        data.push(self.text_background_color_index);
-        data.extend_from_slice(&self.data.encode());
-        data
+        w.write_all(&[ExtensionBlock::INTRODUCER, Self::LABEL, Self::BLOCK_SIZE])?;
+        w.write_all(&self.text_grid_left_position.to_le_bytes())?;
+        w.write_all(&self.text_grid_top_position.to_le_bytes())?;
+        w.write_all(&self.text_grid_width.to_le_bytes())?;
+        w.write_all(&self.text_grid_height.to_le_bytes())?;
+        w.write_all(&[
+            self.character_cell_width,
+            self.character_cell_height,
+            self.text_foreground_color_index,
+            self.text_background_color_index,
+        ])?;
+        self.data.write(w)
     }
 }
 
 impl GraphicControlExtension {
-    fn encode(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        data.push(ExtensionBlock::INTRODUCER);
-        data.push(Self::LABEL);
-        data.push(Self::BLOCK_SIZE);
-        data.push(self.packed_fields);
-        data.push((self.delay_time >> 8) as u8);
-        data.push(((self.delay_time << 8) >> 8) as u8);
-        data.push(self.transparent_color_index);
-        data.push(DataSubBlock::BLOCK_TERMINATOR);
-        data
+    /// The number of bytes written, block terminator included.
+    const LEN: usize = 4 + Self::BLOCK_SIZE as usize;
+
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
+        w.write_all(&[
+            ExtensionBlock::INTRODUCER,
+            Self::LABEL,
+            Self::BLOCK_SIZE,
+            self.packed_fields,
+        ])?;
+        w.write_all(&self.delay_time.to_le_bytes())?;
+        w.write_all(&[self.transparent_color_index, DataSubBlock::BLOCK_TERMINATOR])
     }
 }
 
 impl<'a> CommentExtension<'a> {
-    fn encode(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        data.push(ExtensionBlock::INTRODUCER);
-        data.push(Self::LABEL);
-        data.extend_from_slice(&self.data.encode());
-        data
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
+        self.data.check(w.offset + 2)?;
+        w.write_all(&[ExtensionBlock::INTRODUCER, Self::LABEL])?;
+        self.data.write(w)
     }
 }
 
 impl<'a> ImageDescriptor<'a> {
-    fn encode(&self, version: &Version) -> Vec<u8> {
-        let mut data = Vec::new();
-        if let Some(graphic_control_extension) = &self.graphic_control_extension {
-            if *version == Version::V87a {
-                info!("Discarding graphic control extension because it's not supported in the target version '{:?}'", version);
-            } else {
-                data.extend_from_slice(&graphic_control_extension.encode());
-            }
+    fn write<W: Write>(&self, w: &mut Output<W>, version: &Version) -> Result<(), GifError> {
+        // Checked before the graphic control extension, so that a mismatch doesn't leave it
+        // written without the image it belongs to.
+        let graphic_control_extension = self
+            .graphic_control_extension
+            .as_ref()
+            .filter(|_| *version != Version::V87a);
+        if self.graphic_control_extension.is_some() && graphic_control_extension.is_none() {
+            warn!("Discarding graphic control extension because it's not supported in the target version '{:?}'", version);
+        }
+        let offset =
+            w.offset + graphic_control_extension.map_or(0, |_| GraphicControlExtension::LEN);
+        check_color_table(
+            offset,
+            self.local_color_table_flag(),
+            self.local_color_table_size(),
+            self.local_color_table.as_ref(),
+        )?;
+        let local_color_table_len = self
+            .local_color_table
+            .as_ref()
+            .map_or(0, |table| table.pixels.len());
+        // The separator, the 9 bytes of fields, the local color table and the LZW minimum code
+        // size come first.
+        self.image_data
+            .image_data
+            .check(offset + 10 + local_color_table_len + 1)?;
+        if let Some(graphic_control_extension) = graphic_control_extension {
+            graphic_control_extension.write(w)?;
         }
-        data.push(Self::SEPARATOR);
-        data.push((self.image_left_position >> 8) as u8);
-        data.push(((self.image_left_position << 8) >> 8) as u8);
-        data.push((self.image_top_position >> 8) as u8);
-        data.push(((self.image_top_position << 8) >> 8) as u8);
-        data.push((self.image_width >> 8) as u8);
-        data.push(((self.image_width << 8) >> 8) as u8);
-        data.push((self.image_height >> 8) as u8);
-        data.push(((self.image_height << 8) >> 8) as u8);
-        data.push(self.packed_fields);
+        w.write_all(&[Self::SEPARATOR])?;
+        w.write_all(&self.image_left_position.to_le_bytes())?;
+        w.write_all(&self.image_top_position.to_le_bytes())?;
+        w.write_all(&self.image_width.to_le_bytes())?;
+        w.write_all(&self.image_height.to_le_bytes())?;
+        w.write_all(&[self.packed_fields])?;
         if let Some(local_color_table) = &self.local_color_table {
-            data.extend_from_slice(local_color_table.pixels);
+            w.write_all(&local_color_table.pixels)?;
         }
-        data.extend_from_slice(&self.image_data.encode());
-        data
+        self.image_data.write(w)
     }
 }
 
 impl<'a> TableBasedImageData<'a> {
-    fn encode(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        data.push(self.lzw_minimum_code_size);
-        data.extend_from_slice(&self.image_data.encode());
-        data
+    fn write<W: Write>(&self, w: &mut Output<W>) -> Result<(), GifError> {
+        w.write_all(&[self.lzw_minimum_code_size])?;
+        self.image_data.write(w)
     }
 }
@@ -0,0 +1,219 @@
+use crate::decoder::Limit;
+use std::{error, fmt, io};
+
+/// Everything that can go wrong while decoding or encoding a GIF data stream.
+///
+/// Every variant carries the byte offset at which the problem was detected, so callers can both
+/// match on the failure kind and point at the offending bytes. Offsets are relative to the start
+/// of the data stream, except for the errors found while decompressing image data, whose offsets
+/// are relative to the start of the (sub-block payloads of the) compressed image data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GifError {
+    /// The data stream ended while `needed` more bytes were expected.
+    UnexpectedEof { offset: usize, needed: usize },
+    /// The first 3 bytes aren't `b"GIF"`.
+    InvalidSignature { offset: usize, found: Vec<u8> },
+    /// The version is neither `b"87a"` nor `b"89a"`.
+    InvalidVersion { offset: usize, found: Vec<u8> },
+    /// A fixed-size block declared a size different from the one mandated by the spec.
+    BadBlockSize {
+        offset: usize,
+        expected: u8,
+        found: u8,
+    },
+    /// A block that must be terminated by a zero-length sub-block isn't.
+    BadBlockTerminator { offset: usize, found: u8 },
+    /// An extension block uses a label this decoder doesn't know about.
+    UnknownLabel { offset: usize, label: u8 },
+    /// A byte that introduces neither an extension, an image nor the trailer.
+    UnknownBlock { offset: usize, byte: u8 },
+    /// The LZW minimum code size can't produce 8-bit color indices.
+    InvalidMinimumCodeSize { offset: usize, size: u8 },
+    /// A code that is neither in the code table nor the next one to be defined.
+    InvalidCode { offset: usize, code: u16 },
+    /// The image data ended before producing `expected` color indices.
+    ImageDataTooShort {
+        offset: usize,
+        expected: usize,
+        decoded: usize,
+    },
+    /// The image data produces more than `expected` color indices.
+    ImageDataTooLong { offset: usize, expected: usize },
+    /// A color index that doesn't fit in the LZW minimum code size. The offset is the position of
+    /// the index in the uncompressed image.
+    ColorIndexOutOfRange { offset: usize, index: u8 },
+    /// A size or a count exceeds a limit set with [`crate::decoder::DecodeOptions`].
+    LimitExceeded {
+        offset: usize,
+        limit: Limit,
+        value: u64,
+        max: u64,
+    },
+    /// The flag and size in the packed fields describe a color table of `expected` colors, while
+    /// the color table being encoded has `found` colors. `None` means there's no table.
+    ColorTableMismatch {
+        offset: usize,
+        expected: Option<usize>,
+        found: Option<usize>,
+    },
+    /// A sub-block being encoded has a `block_size` different from the length of its data.
+    SubBlockSizeMismatch {
+        offset: usize,
+        block_size: u8,
+        len: usize,
+    },
+    /// A sub-block being encoded has no data, which would make it the block terminator.
+    EmptySubBlock { offset: usize },
+    /// Reading or writing the data stream failed.
+    Io {
+        offset: usize,
+        kind: io::ErrorKind,
+        message: String,
+    },
+}
+
+impl GifError {
+    /// The byte offset at which the error was detected.
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::UnexpectedEof { offset, .. }
+            | Self::InvalidSignature { offset, .. }
+            | Self::InvalidVersion { offset, .. }
+            | Self::BadBlockSize { offset, .. }
+            | Self::BadBlockTerminator { offset, .. }
+            | Self::UnknownLabel { offset, .. }
+            | Self::UnknownBlock { offset, .. }
+            | Self::InvalidMinimumCodeSize { offset, .. }
+            | Self::InvalidCode { offset, .. }
+            | Self::ImageDataTooShort { offset, .. }
+            | Self::ImageDataTooLong { offset, .. }
+            | Self::ColorIndexOutOfRange { offset, .. }
+            | Self::LimitExceeded { offset, .. }
+            | Self::ColorTableMismatch { offset, .. }
+            | Self::SubBlockSizeMismatch { offset, .. }
+            | Self::EmptySubBlock { offset }
+            | Self::Io { offset, .. } => *offset,
+        }
+    }
+}
+
+impl fmt::Display for GifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof { offset, needed } => write!(
+                f,
+                "unexpected end of data at offset {}: {} more byte(s) needed",
+                offset, needed
+            ),
+            Self::InvalidSignature { offset, found } => write!(
+                f,
+                "invalid signature at offset {}: expected '{:x?}', got '{:x?}'",
+                offset,
+                crate::SIGNATURE,
+                found
+            ),
+            Self::InvalidVersion { offset, found } => write!(
+                f,
+                "invalid GIF version at offset {}: expected either b\"87a\" or b\"89a\", got '{:x?}'",
+                offset, found
+            ),
+            Self::BadBlockSize {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "invalid block size at offset {}: expected '{}', got '{}'",
+                offset, expected, found
+            ),
+            Self::BadBlockTerminator { offset, found } => write!(
+                f,
+                "invalid block terminator at offset {}: expected '{}', got '{}'",
+                offset,
+                crate::DataSubBlock::BLOCK_TERMINATOR,
+                found
+            ),
+            Self::UnknownLabel { offset, label } => write!(
+                f,
+                "invalid extension block label '0x{:x}' at offset {}",
+                label, offset
+            ),
+            Self::UnknownBlock { offset, byte } => {
+                write!(f, "unknown byte 0x{:x} at offset {}", byte, offset)
+            }
+            Self::InvalidMinimumCodeSize { offset, size } => write!(
+                f,
+                "invalid LZW minimum code size at offset {}: expected a value between 2 and 8, got '{}'",
+                offset, size
+            ),
+            Self::InvalidCode { offset, code } => {
+                write!(f, "invalid LZW code {} at offset {}", code, offset)
+            }
+            Self::ImageDataTooShort {
+                offset,
+                expected,
+                decoded,
+            } => write!(
+                f,
+                "image data ended at offset {} after {} of {} color indices",
+                offset, decoded, expected
+            ),
+            Self::ImageDataTooLong { offset, expected } => write!(
+                f,
+                "image data at offset {} decompresses to more than {} color indices",
+                offset, expected
+            ),
+            Self::ColorIndexOutOfRange { offset, index } => write!(
+                f,
+                "color index {} at offset {} doesn't fit in the LZW minimum code size",
+                index, offset
+            ),
+            Self::LimitExceeded {
+                offset,
+                limit,
+                value,
+                max,
+            } => write!(
+                f,
+                "{} at offset {} exceeds the limit: {} > {}",
+                limit, offset, value, max
+            ),
+            Self::ColorTableMismatch {
+                offset,
+                expected,
+                found,
+            } => {
+                let colors = |colors: &Option<usize>| match colors {
+                    Some(colors) => format!("{} colors", colors),
+                    None => "none".to_string(),
+                };
+                write!(
+                    f,
+                    "color table mismatch at offset {}: {} declared by the packed fields, {} found",
+                    offset,
+                    colors(expected),
+                    colors(found)
+                )
+            }
+            Self::SubBlockSizeMismatch {
+                offset,
+                block_size,
+                len,
+            } => write!(
+                f,
+                "sub-block at offset {} has a size of {} but {} bytes of data",
+                offset, block_size, len
+            ),
+            Self::EmptySubBlock { offset } => write!(
+                f,
+                "sub-block at offset {} has no data, which would terminate the block",
+                offset
+            ),
+            Self::Io {
+                offset, message, ..
+            } => write!(f, "I/O error at offset {}: {}", offset, message),
+        }
+    }
+}
+
+impl error::Error for GifError {}
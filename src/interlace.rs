@@ -0,0 +1,40 @@
+//! The four-pass interlace scheme: rows are stored every 8th row starting from row 0, then every
+//! 8th row starting from row 4, then every 4th row starting from row 2, and finally every 2nd row
+//! starting from row 1.
+
+/// (first row, step, preview height) of every pass. The preview height is how many rows a row of
+/// the pass stands for until the later passes fill the gap below it.
+pub(crate) const PASSES: [(usize, usize, usize); 4] = [(0, 8, 8), (4, 8, 4), (2, 4, 2), (1, 2, 1)];
+
+/// The row of the image each stored row of an interlaced image of `height` rows belongs to, in
+/// storage order.
+pub fn rows(height: usize) -> impl Iterator<Item = usize> {
+    PASSES
+        .iter()
+        .flat_map(move |&(first, step, _)| (first..height).step_by(step))
+}
+
+/// Reorders the rows of `indices` from storage order to natural order.
+pub fn deinterlace(indices: &[u8], width: usize) -> Vec<u8> {
+    if width == 0 {
+        return indices.to_vec();
+    }
+    let mut output = vec![0; indices.len()];
+    let height = indices.len() / width;
+    for (row, y) in indices.chunks(width).zip(rows(height)) {
+        output[y * width..(y + 1) * width].copy_from_slice(row);
+    }
+    output
+}
+
+/// Reorders the rows of `indices` from natural order to storage order.
+pub fn interlace(indices: &[u8], width: usize) -> Vec<u8> {
+    if width == 0 {
+        return indices.to_vec();
+    }
+    let height = indices.len() / width;
+    rows(height)
+        .flat_map(|y| &indices[y * width..(y + 1) * width])
+        .copied()
+        .collect()
+}
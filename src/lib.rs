@@ -1,29 +1,138 @@
+pub mod assembly;
+pub mod convert;
 pub mod decoder;
+pub mod dump;
 pub mod encoder;
+mod error;
+pub mod interlace;
+pub mod lzw;
+mod netscape;
+mod owned;
+pub mod progressive;
+pub mod render;
+pub mod validate;
 
-use std::fmt;
+pub use error::GifError;
+pub use netscape::NetscapeExtension;
+
+use std::{borrow::Cow, convert::TryFrom, fmt, time::Duration};
 
 #[derive(Debug, Default)]
-pub(crate) struct Context {
+pub(crate) struct Context<'a> {
     pub(crate) offset: usize,
     pub(crate) graphic_control_extension: Option<GraphicControlExtension>,
+    /// A block that has been decoded but is yet to be returned, because the orphaned graphic
+    /// control extension preceding it had to be returned first.
+    pub(crate) pending_block: Option<Block<'a>>,
+    pub(crate) trailer_found: bool,
+    /// The span of the trailer, which `trailer_found` doesn't imply: decoding leniently treats
+    /// the end of the data stream as the trailer.
+    pub(crate) trailer: Option<Span>,
+    pub(crate) options: decoder::DecodeOptions,
+    /// How many images have been found so far, and their total number of pixels.
+    pub(crate) frames: usize,
+    pub(crate) total_pixels: u64,
+    /// The deviations from the spec recovered from when decoding leniently.
+    pub(crate) warnings: Vec<GifError>,
+    /// Whether the data stream is being pushed to the decoder, so that running out of bytes
+    /// doesn't mean it's truncated.
+    pub(crate) incremental: bool,
+    /// The complete sub-blocks of the data sub-blocks being pushed, for the next attempt at
+    /// decoding them to resume after them rather than from the start.
+    pub(crate) received_sub_blocks: Option<ReceivedSubBlocks>,
 }
 
+/// The sub-blocks of the data sub-blocks starting at `start` received so far, ending at `end`.
 #[derive(Debug)]
+pub(crate) struct ReceivedSubBlocks {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) blocks: Vec<DataSubBlock<'static>>,
+}
+
+#[derive(Debug, Clone)]
 pub struct GifData<'a> {
     pub version: Version,
     pub logical_screen_descriptor: LogicalScreenDescriptor<'a>,
-    pub application_extensions: Vec<ApplicationExtension<'a>>,
-    pub comment_extensions: Vec<CommentExtension<'a>>,
-    pub graphic_rendering_blocks: Vec<GraphicRenderingBlock<'a>>,
+    /// Every block between the logical screen descriptor and the trailer, in data stream order.
+    pub blocks: Vec<Block<'a>>,
+    /// The trailer byte, `None` when the document wasn't decoded or was decoded leniently from a
+    /// data stream missing it.
+    pub trailer: Option<Span>,
 }
 
-#[derive(Debug, PartialEq)]
+impl<'a> GifData<'a> {
+    pub fn application_extensions(&self) -> impl Iterator<Item = &ApplicationExtension<'a>> {
+        self.blocks.iter().filter_map(|block| match block {
+            Block::Application(ext) => Some(ext),
+            _ => None,
+        })
+    }
+
+    pub fn comment_extensions(&self) -> impl Iterator<Item = &CommentExtension<'a>> {
+        self.blocks.iter().filter_map(|block| match block {
+            Block::Comment(ext) => Some(ext),
+            _ => None,
+        })
+    }
+
+    pub fn graphic_rendering_blocks(&self) -> impl Iterator<Item = &GraphicRenderingBlock<'a>> {
+        self.blocks.iter().filter_map(|block| match block {
+            Block::GraphicRendering(block) => Some(block),
+            _ => None,
+        })
+    }
+}
+
+/// A top-level block of the data stream. Graphic control extensions are attached to the graphic
+/// rendering block immediately following them, the ones that aren't (because they're followed by
+/// another extension or by the trailer) are kept as blocks of their own.
+#[derive(Debug, Clone)]
+pub enum Block<'a> {
+    Application(ApplicationExtension<'a>),
+    Comment(CommentExtension<'a>),
+    GraphicRendering(GraphicRenderingBlock<'a>),
+    /// A graphic control extension not immediately followed by a graphic rendering block.
+    GraphicControl(GraphicControlExtension),
+    /// An extension with a label this library doesn't know about, kept as it is.
+    Unknown {
+        label: u8,
+        data: DataSubBlocks<'a>,
+        span: Option<Span>,
+    },
+}
+
+/// A range of byte offsets in the data stream a block was decoded from, `end` excluded.
+///
+/// Spans describe the bytes as they were decoded: they aren't updated when a block is edited, and
+/// the encoder ignores them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.start..self.end
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Version {
     V87a,
     V89a,
 }
 
+#[derive(Clone)]
 pub struct LogicalScreenDescriptor<'a> {
     pub logical_screen_width: u16,
     pub logical_screen_height: u16,
@@ -31,9 +140,24 @@ pub struct LogicalScreenDescriptor<'a> {
     pub background_color_index: u8,
     pub pixel_aspect_ratio: u8,
     pub global_color_table: Option<ColorTable<'a>>,
+    /// The 7 bytes of the descriptor, without the global color table.
+    pub span: Option<Span>,
 }
 
 impl<'a> LogicalScreenDescriptor<'a> {
+    /// A logical screen without global color table.
+    pub fn new(logical_screen_width: u16, logical_screen_height: u16) -> Self {
+        Self {
+            logical_screen_width,
+            logical_screen_height,
+            packed_fields: 0,
+            background_color_index: 0,
+            pixel_aspect_ratio: 0,
+            global_color_table: None,
+            span: None,
+        }
+    }
+
     // 1 bit (MSB)
     pub fn global_color_table_flag(&self) -> u8 {
         self.packed_fields >> 7
@@ -57,13 +181,37 @@ impl<'a> LogicalScreenDescriptor<'a> {
     pub fn aspect_ratio(&self) -> f32 {
         (self.pixel_aspect_ratio as f32 + 15.0) / 64.0
     }
+
+    /// Sets or removes the global color table, updating its flag and size accordingly.
+    pub fn set_global_color_table(&mut self, global_color_table: Option<ColorTable<'a>>) {
+        match &global_color_table {
+            Some(table) => {
+                set_bits(&mut self.packed_fields, 7, 1, 1);
+                set_bits(&mut self.packed_fields, 0, 3, table.size_bits());
+            }
+            None => {
+                set_bits(&mut self.packed_fields, 7, 1, 0);
+                set_bits(&mut self.packed_fields, 0, 3, 0);
+            }
+        }
+        self.global_color_table = global_color_table;
+    }
+
+    /// Only the 3 least significant bits of `color_resolution` are used.
+    pub fn set_color_resolution(&mut self, color_resolution: u8) {
+        set_bits(&mut self.packed_fields, 4, 3, color_resolution);
+    }
+
+    pub fn set_sort_flag(&mut self, sort_flag: bool) {
+        set_bits(&mut self.packed_fields, 3, 1, sort_flag as u8);
+    }
 }
 
 impl<'a> fmt::Debug for LogicalScreenDescriptor<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("LogicalScreenDescriptor")
             .field("logical_screen_width", &self.logical_screen_width)
-            .field("logical_screen_height", &self.logical_screen_width)
+            .field("logical_screen_height", &self.logical_screen_height)
             .field("global_color_table_flag", &self.global_color_table_flag())
             .field("color_resolution", &self.color_resolution())
             .field("sort_flag", &self.sort_flag())
@@ -72,19 +220,52 @@ impl<'a> fmt::Debug for LogicalScreenDescriptor<'a> {
             .field("background_color_index", &self.background_color_index)
             .field("pixel_aspect_ratio", &self.pixel_aspect_ratio)
             .field("global_color_table", &self.global_color_table)
+            .field("span", &self.span)
             .finish()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ColorTable<'a> {
-    pixels: &'a [u8],
+    pixels: Cow<'a, [u8]>,
+    /// Where the block was found in the decoded bytes, `None` if it wasn't decoded.
+    pub span: Option<Span>,
 }
 
 impl<'a> ColorTable<'a> {
+    /// A color table made of RGB triplets. Returns `None` unless the number of colors is a power
+    /// of 2 between 2 and 256, the only sizes a GIF can describe.
+    pub fn new(pixels: impl Into<Cow<'a, [u8]>>) -> Option<Self> {
+        let pixels = pixels.into();
+        let len = pixels.len() / 3;
+        if pixels.len() % 3 != 0 || !len.is_power_of_two() || !(2..=256).contains(&len) {
+            return None;
+        }
+        Some(Self { pixels, span: None })
+    }
+
     pub fn get_pixel(&self, idx: usize) -> &[u8] {
         &self.pixels[idx * 3..idx * 3 + 3]
     }
+
+    /// The RGB triplets, one after the other.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// The number of colors.
+    pub fn len(&self) -> usize {
+        self.pixels.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
+    }
+
+    /// The value of the size field in the packed fields describing this table.
+    pub(crate) fn size_bits(&self) -> u8 {
+        (self.len().trailing_zeros() as u8).saturating_sub(1)
+    }
 }
 
 #[derive(Debug)]
@@ -93,6 +274,11 @@ pub(crate) enum ExtensionBlock<'a> {
     Comment(CommentExtension<'a>),
     PlainText(PlainTextExtension<'a>),
     Application(ApplicationExtension<'a>),
+    Unknown {
+        label: u8,
+        data: DataSubBlocks<'a>,
+        span: Option<Span>,
+    },
 }
 
 impl<'a> ExtensionBlock<'a> {
@@ -106,36 +292,58 @@ impl<'a> From<&ExtensionBlock<'a>> for &'static str {
             ExtensionBlock::Comment(_) => "comment",
             ExtensionBlock::PlainText(_) => "plain text",
             ExtensionBlock::Application(_) => "application",
+            ExtensionBlock::Unknown { .. } => "unknown",
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum GraphicRenderingBlock<'a> {
     PlainText(PlainTextExtension<'a>),
     Image(ImageDescriptor<'a>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DataSubBlock<'a> {
     pub block_size: u8,
-    pub data: &'a [u8],
+    pub data: Cow<'a, [u8]>,
+    /// The size byte and the data.
+    pub span: Option<Span>,
 }
 
 impl<'a> DataSubBlock<'a> {
     pub(crate) const BLOCK_TERMINATOR: u8 = 0x00;
+    pub(crate) const MAX_SIZE: usize = 255;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DataSubBlocks<'a> {
     pub blocks: Vec<DataSubBlock<'a>>,
 }
 
-#[derive(Debug)]
+impl DataSubBlocks<'static> {
+    /// Splits `data` into as many full (255 bytes) sub-blocks as possible, followed by the
+    /// remainder.
+    pub fn from_data(data: &[u8]) -> Self {
+        let blocks = data
+            .chunks(DataSubBlock::MAX_SIZE)
+            .map(|chunk| DataSubBlock {
+                block_size: chunk.len() as u8,
+                data: Cow::Owned(chunk.to_vec()),
+                span: None,
+            })
+            .collect();
+        Self { blocks }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ApplicationExtension<'a> {
-    pub identifier: &'a [u8],
-    pub authentication_code: &'a [u8],
+    pub identifier: Cow<'a, [u8]>,
+    pub authentication_code: Cow<'a, [u8]>,
     pub data: DataSubBlocks<'a>,
+    /// From the extension introducer to the block terminator.
+    pub span: Option<Span>,
 }
 
 impl<'a> ApplicationExtension<'a> {
@@ -143,7 +351,7 @@ impl<'a> ApplicationExtension<'a> {
     pub(crate) const BLOCK_SIZE: u8 = 11;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PlainTextExtension<'a> {
     pub text_grid_left_position: u16,
     pub text_grid_top_position: u16,
@@ -155,6 +363,9 @@ pub struct PlainTextExtension<'a> {
     pub text_background_color_index: u8,
     pub data: DataSubBlocks<'a>,
     pub graphic_control_extension: Option<GraphicControlExtension>,
+    /// From the extension introducer to the block terminator, without the graphic control
+    /// extension.
+    pub span: Option<Span>,
 }
 
 impl<'a> PlainTextExtension<'a> {
@@ -162,10 +373,13 @@ impl<'a> PlainTextExtension<'a> {
     pub(crate) const BLOCK_SIZE: u8 = 12;
 }
 
+#[derive(Clone)]
 pub struct GraphicControlExtension {
     packed_fields: u8,
     pub delay_time: u16,
     pub transparent_color_index: u8,
+    /// From the extension introducer to the block terminator.
+    pub span: Option<Span>,
 }
 
 impl GraphicControlExtension {
@@ -174,9 +388,37 @@ impl GraphicControlExtension {
 }
 
 impl GraphicControlExtension {
+    /// Creates an extension with the user input flag unset. The transparent color flag is set
+    /// if there's a `transparent_color_index`. `delay` is rounded down to hundredths of a second,
+    /// see [`GraphicControlExtension::set_delay`].
+    ///
+    /// # Panics
+    ///
+    /// Like [`GraphicControlExtension::set_disposal_method`].
+    pub fn new(
+        disposal_method: DisposalMethod,
+        delay: Duration,
+        transparent_color_index: Option<u8>,
+    ) -> Self {
+        let mut ext = Self {
+            packed_fields: 0,
+            delay_time: 0,
+            transparent_color_index: transparent_color_index.unwrap_or(0),
+            span: None,
+        };
+        ext.set_disposal_method(disposal_method);
+        ext.set_delay(delay);
+        ext.set_transparent_color_flag(transparent_color_index.is_some());
+        ext
+    }
+
     // 3 bits
-    pub fn disposal_method(&self) -> u8 {
-        (self.packed_fields << 3) >> 5
+    pub fn disposal_method(&self) -> DisposalMethod {
+        match DisposalMethod::try_from((self.packed_fields << 3) >> 5) {
+            Ok(disposal_method) => disposal_method,
+            // 3 bits can't hold more than 7.
+            Err(value) => unreachable!("disposal method {} out of 3 bits", value),
+        }
     }
 
     // 1 bit
@@ -188,6 +430,40 @@ impl GraphicControlExtension {
     pub fn transparent_color_flag(&self) -> u8 {
         (self.packed_fields << 7) >> 7
     }
+
+    /// # Panics
+    ///
+    /// If `disposal_method` is [`DisposalMethod::Reserved`] with a value outside 4 to 7, which
+    /// would read back as a different disposal method.
+    pub fn set_disposal_method(&mut self, disposal_method: DisposalMethod) {
+        if let DisposalMethod::Reserved(value) = disposal_method {
+            assert!(
+                DisposalMethod::RESERVED.contains(&value),
+                "reserved disposal methods go from 4 to 7, got {}",
+                value
+            );
+        }
+        set_bits(&mut self.packed_fields, 2, 3, disposal_method.into());
+    }
+
+    /// `delay_time` as a [`Duration`].
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_time as u64 * 10)
+    }
+
+    /// Sets `delay_time`, which is in hundredths of a second: `delay` is rounded down, and
+    /// saturates at `u16::MAX` hundredths.
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay_time = (delay.as_millis() / 10).min(u16::MAX as u128) as u16;
+    }
+
+    pub fn set_user_input_flag(&mut self, user_input_flag: bool) {
+        set_bits(&mut self.packed_fields, 1, 1, user_input_flag as u8);
+    }
+
+    pub fn set_transparent_color_flag(&mut self, transparent_color_flag: bool) {
+        set_bits(&mut self.packed_fields, 0, 1, transparent_color_flag as u8);
+    }
 }
 
 impl fmt::Debug for GraphicControlExtension {
@@ -198,19 +474,72 @@ impl fmt::Debug for GraphicControlExtension {
             .field("transparent_color_flag", &self.transparent_color_flag())
             .field("delay_time", &self.delay_time)
             .field("transparent_color_index", &self.transparent_color_index)
+            .field("span", &self.span)
             .finish()
     }
 }
 
-#[derive(Debug)]
+/// What a viewer should do with the area of a graphic rendering block once it has been
+/// displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposalMethod {
+    /// The viewer isn't required to do anything.
+    Unspecified,
+    /// The graphic is left in place.
+    None,
+    /// The area is restored to the background color.
+    RestoreBackground,
+    /// The area is restored to what was there before the graphic was rendered.
+    RestorePrevious,
+    /// One of the values 4 to 7, reserved by the spec. Converting from a `u8` checks the range,
+    /// and [`GraphicControlExtension::set_disposal_method`] panics on values outside of it.
+    Reserved(u8),
+}
+
+impl DisposalMethod {
+    const RESERVED: std::ops::RangeInclusive<u8> = 4..=7;
+}
+
+/// Fails with the value if it doesn't fit in the 3 bits of the packed fields.
+impl TryFrom<u8> for DisposalMethod {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Unspecified),
+            1 => Ok(Self::None),
+            2 => Ok(Self::RestoreBackground),
+            3 => Ok(Self::RestorePrevious),
+            value if Self::RESERVED.contains(&value) => Ok(Self::Reserved(value)),
+            value => Err(value),
+        }
+    }
+}
+
+impl From<DisposalMethod> for u8 {
+    fn from(disposal_method: DisposalMethod) -> Self {
+        match disposal_method {
+            DisposalMethod::Unspecified => 0,
+            DisposalMethod::None => 1,
+            DisposalMethod::RestoreBackground => 2,
+            DisposalMethod::RestorePrevious => 3,
+            DisposalMethod::Reserved(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct CommentExtension<'a> {
     pub data: DataSubBlocks<'a>,
+    /// From the extension introducer to the block terminator.
+    pub span: Option<Span>,
 }
 
 impl<'a> CommentExtension<'a> {
     pub(crate) const LABEL: u8 = 0xfe;
 }
 
+#[derive(Clone)]
 pub struct ImageDescriptor<'a> {
     pub image_left_position: u16,
     pub image_top_position: u16,
@@ -220,11 +549,35 @@ pub struct ImageDescriptor<'a> {
     pub local_color_table: Option<ColorTable<'a>>,
     pub image_data: TableBasedImageData<'a>,
     pub graphic_control_extension: Option<GraphicControlExtension>,
+    /// The 10 bytes of the descriptor, from the image separator to the packed fields. The local
+    /// color table and the image data have spans of their own.
+    pub span: Option<Span>,
 }
 
 impl<'a> ImageDescriptor<'a> {
     const SEPARATOR: u8 = 0x2c;
 
+    /// An image without local color table, not interlaced, using the global color table.
+    pub fn new(
+        image_left_position: u16,
+        image_top_position: u16,
+        image_width: u16,
+        image_height: u16,
+        image_data: TableBasedImageData<'a>,
+    ) -> Self {
+        Self {
+            image_left_position,
+            image_top_position,
+            image_width,
+            image_height,
+            packed_fields: 0,
+            local_color_table: None,
+            image_data,
+            graphic_control_extension: None,
+            span: None,
+        }
+    }
+
     pub fn local_color_table_flag(&self) -> u8 {
         self.packed_fields >> 7
     }
@@ -240,6 +593,31 @@ impl<'a> ImageDescriptor<'a> {
     pub fn local_color_table_size(&self) -> u8 {
         (self.packed_fields << 5) >> 5
     }
+
+    /// Sets or removes the local color table, updating its flag and size accordingly.
+    pub fn set_local_color_table(&mut self, local_color_table: Option<ColorTable<'a>>) {
+        match &local_color_table {
+            Some(table) => {
+                set_bits(&mut self.packed_fields, 7, 1, 1);
+                set_bits(&mut self.packed_fields, 0, 3, table.size_bits());
+            }
+            None => {
+                set_bits(&mut self.packed_fields, 7, 1, 0);
+                set_bits(&mut self.packed_fields, 0, 3, 0);
+            }
+        }
+        self.local_color_table = local_color_table;
+    }
+
+    /// Only sets the flag, the image data is expected to already be in the matching row order.
+    /// See [`encoder::Encoder::set_interlace`] to interlace images while encoding them.
+    pub fn set_interlace_flag(&mut self, interlace_flag: bool) {
+        set_bits(&mut self.packed_fields, 6, 1, interlace_flag as u8);
+    }
+
+    pub fn set_sort_flag(&mut self, sort_flag: bool) {
+        set_bits(&mut self.packed_fields, 5, 1, sort_flag as u8);
+    }
 }
 
 impl<'a> fmt::Debug for ImageDescriptor<'a> {
@@ -256,16 +634,36 @@ impl<'a> fmt::Debug for ImageDescriptor<'a> {
             .field("local_color_table", &self.local_color_table)
             .field("image_data", &self.image_data)
             .field("graphic_control_extension", &self.graphic_control_extension)
+            .field("span", &self.span)
             .finish()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TableBasedImageData<'a> {
     pub lzw_minimum_code_size: u8,
     pub image_data: DataSubBlocks<'a>,
+    /// From the LZW minimum code size to the block terminator.
+    pub span: Option<Span>,
+}
+
+/// The number of colors of the color table described by a color table flag and size, `None` if
+/// the flag isn't set.
+pub(crate) fn color_table_len(flag: u8, size_bits: u8) -> Option<usize> {
+    if flag == 1 {
+        Some(2 << size_bits)
+    } else {
+        None
+    }
+}
+
+/// Overwrites the `width` bits of `packed_fields` starting at bit `shift` (0 being the LSB) with
+/// the least significant bits of `value`.
+fn set_bits(packed_fields: &mut u8, shift: u8, width: u8, value: u8) {
+    let mask = ((1u16 << width) - 1) as u8;
+    *packed_fields = (*packed_fields & !(mask << shift)) | ((value & mask) << shift);
 }
 
-pub(crate) const SIGNATURE: &'static [u8] = b"GIF";
+pub(crate) const SIGNATURE: &[u8] = b"GIF";
 
 pub(crate) const TRAILER: u8 = 0x3b;
@@ -0,0 +1,385 @@
+//! Variable-length-code LZW, as used by GIF for table based image data.
+//!
+//! Codes are packed LSB first. The code width starts at `lzw_minimum_code_size + 1` bits and
+//! grows by one every time the next code to be assigned doesn't fit anymore, up to 12 bits.
+//! Once the table is full (4096 entries) no more entries are added until a clear code shows up
+//! (the so called deferred clear).
+
+use crate::{interlace, DataSubBlocks, GifError, ImageDescriptor, TableBasedImageData};
+use log::debug;
+use std::collections::HashMap;
+
+pub(crate) const MAX_CODE_SIZE: u8 = 12;
+const TABLE_SIZE: usize = 1 << MAX_CODE_SIZE;
+
+/// An incremental LZW decoder, compressed bytes go in and color indices come out.
+pub struct Decoder {
+    minimum_code_size: u8,
+    code_size: u8,
+    next_code: u16,
+    previous_code: Option<u16>,
+    prefixes: Vec<u16>,
+    suffixes: Vec<u8>,
+    lengths: Vec<u16>,
+    bits: u32,
+    bit_count: u8,
+    consumed: usize,
+    finished: bool,
+    stats: Stats,
+}
+
+/// What a [`Decoder`] has decoded so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Every code, including the clear and end of information codes.
+    pub codes: usize,
+    pub clear_codes: usize,
+    /// The width in bits of the widest code.
+    pub max_code_size: u8,
+}
+
+impl Decoder {
+    pub fn new(minimum_code_size: u8) -> Result<Self, GifError> {
+        if !(2..=8).contains(&minimum_code_size) {
+            return Err(GifError::InvalidMinimumCodeSize {
+                offset: 0,
+                size: minimum_code_size,
+            });
+        }
+        let mut s = Self {
+            minimum_code_size,
+            code_size: 0,
+            next_code: 0,
+            previous_code: None,
+            prefixes: vec![0; TABLE_SIZE],
+            suffixes: vec![0; TABLE_SIZE],
+            lengths: vec![0; TABLE_SIZE],
+            bits: 0,
+            bit_count: 0,
+            consumed: 0,
+            finished: false,
+            stats: Stats::default(),
+        };
+        for code in 0..s.clear_code() {
+            s.suffixes[code as usize] = code as u8;
+            s.lengths[code as usize] = 1;
+        }
+        s.reset();
+        Ok(s)
+    }
+
+    fn clear_code(&self) -> u16 {
+        1 << self.minimum_code_size
+    }
+
+    fn end_code(&self) -> u16 {
+        self.clear_code() + 1
+    }
+
+    fn reset(&mut self) {
+        self.code_size = self.minimum_code_size + 1;
+        self.next_code = self.end_code() + 1;
+        self.previous_code = None;
+    }
+
+    /// How many bytes of compressed data have been decoded so far.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Whether the end of information code has been found.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Decompresses `input`, appending the color indices to `output`. `output` is never allowed
+    /// to grow past `max_len`. Bytes after the end of information code are ignored.
+    pub fn decode_bytes(
+        &mut self,
+        input: &[u8],
+        output: &mut Vec<u8>,
+        max_len: usize,
+    ) -> Result<(), GifError> {
+        for &byte in input {
+            if self.finished {
+                break;
+            }
+            self.bits |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+            while !self.finished && self.bit_count >= self.code_size {
+                let code = (self.bits & ((1 << self.code_size) - 1)) as u16;
+                self.bits >>= self.code_size;
+                self.bit_count -= self.code_size;
+                self.decode_code(code, output, max_len)?;
+            }
+            self.consumed += 1;
+        }
+        Ok(())
+    }
+
+    fn decode_code(
+        &mut self,
+        code: u16,
+        output: &mut Vec<u8>,
+        max_len: usize,
+    ) -> Result<(), GifError> {
+        self.stats.codes += 1;
+        self.stats.max_code_size = self.stats.max_code_size.max(self.code_size);
+        if code == self.clear_code() {
+            self.stats.clear_codes += 1;
+            self.reset();
+            return Ok(());
+        }
+        if code == self.end_code() {
+            self.finished = true;
+            return Ok(());
+        }
+
+        let previous_code = match self.previous_code {
+            Some(previous_code) => previous_code,
+            None => {
+                if code >= self.clear_code() {
+                    return Err(GifError::InvalidCode {
+                        offset: self.consumed,
+                        code,
+                    });
+                }
+                self.write_string(code, output, max_len)?;
+                self.previous_code = Some(code);
+                return Ok(());
+            }
+        };
+
+        let first_byte = if code < self.next_code {
+            self.write_string(code, output, max_len)?
+        } else if code == self.next_code {
+            // The KwKwK case: the code is the one about to be defined, its string is the previous
+            // string followed by its own first byte.
+            let first_byte = self.write_string(previous_code, output, max_len)?;
+            self.write(&[first_byte], output, max_len)?;
+            first_byte
+        } else {
+            return Err(GifError::InvalidCode {
+                offset: self.consumed,
+                code,
+            });
+        };
+
+        if (self.next_code as usize) < TABLE_SIZE {
+            let next_code = self.next_code as usize;
+            self.prefixes[next_code] = previous_code;
+            self.suffixes[next_code] = first_byte;
+            self.lengths[next_code] = self.lengths[previous_code as usize] + 1;
+            self.next_code += 1;
+            if self.next_code == 1 << self.code_size && self.code_size < MAX_CODE_SIZE {
+                self.code_size += 1;
+            }
+        }
+        self.previous_code = Some(code);
+        Ok(())
+    }
+
+    /// Appends the string of `code` to `output`, returning its first byte.
+    fn write_string(
+        &self,
+        code: u16,
+        output: &mut Vec<u8>,
+        max_len: usize,
+    ) -> Result<u8, GifError> {
+        let len = self.lengths[code as usize] as usize;
+        if output.len() + len > max_len {
+            return Err(GifError::ImageDataTooLong {
+                offset: self.consumed,
+                expected: max_len,
+            });
+        }
+        let start = output.len();
+        output.resize(start + len, 0);
+        let mut code = code;
+        for byte in output[start..].iter_mut().rev() {
+            *byte = self.suffixes[code as usize];
+            code = self.prefixes[code as usize];
+        }
+        Ok(output[start])
+    }
+
+    fn write(&self, bytes: &[u8], output: &mut Vec<u8>, max_len: usize) -> Result<(), GifError> {
+        if output.len() + bytes.len() > max_len {
+            return Err(GifError::ImageDataTooLong {
+                offset: self.consumed,
+                expected: max_len,
+            });
+        }
+        output.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// An LZW encoder, color indices go in and compressed bytes come out.
+///
+/// The code table is cleared as soon as it's full rather than deferring the clear, which every
+/// decoder handles.
+pub struct Encoder {
+    minimum_code_size: u8,
+    code_size: u8,
+    next_code: u16,
+    codes: HashMap<(u16, u8), u16>,
+    bits: u32,
+    bit_count: u8,
+}
+
+impl Encoder {
+    pub fn new(minimum_code_size: u8) -> Result<Self, GifError> {
+        if !(2..=8).contains(&minimum_code_size) {
+            return Err(GifError::InvalidMinimumCodeSize {
+                offset: 0,
+                size: minimum_code_size,
+            });
+        }
+        let mut s = Self {
+            minimum_code_size,
+            code_size: 0,
+            next_code: 0,
+            codes: HashMap::new(),
+            bits: 0,
+            bit_count: 0,
+        };
+        s.reset();
+        Ok(s)
+    }
+
+    fn clear_code(&self) -> u16 {
+        1 << self.minimum_code_size
+    }
+
+    fn end_code(&self) -> u16 {
+        self.clear_code() + 1
+    }
+
+    fn reset(&mut self) {
+        self.code_size = self.minimum_code_size + 1;
+        self.next_code = self.end_code() + 1;
+        self.codes.clear();
+    }
+
+    fn write_code(&mut self, code: u16, output: &mut Vec<u8>) {
+        self.bits |= (code as u32) << self.bit_count;
+        self.bit_count += self.code_size;
+        while self.bit_count >= 8 {
+            output.push(self.bits as u8);
+            self.bits >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    /// Compresses `indices` into a complete code stream, from the initial clear code to the end
+    /// of information code.
+    pub fn encode(mut self, indices: &[u8]) -> Result<Vec<u8>, GifError> {
+        let mut output = Vec::new();
+        let clear_code = self.clear_code();
+        self.write_code(clear_code, &mut output);
+
+        let mut current: Option<u16> = None;
+        for (i, &index) in indices.iter().enumerate() {
+            if index as u16 >= clear_code {
+                return Err(GifError::ColorIndexOutOfRange { offset: i, index });
+            }
+            let prefix = match current {
+                Some(prefix) => prefix,
+                None => {
+                    current = Some(index as u16);
+                    continue;
+                }
+            };
+            if let Some(&code) = self.codes.get(&(prefix, index)) {
+                current = Some(code);
+                continue;
+            }
+            self.write_code(prefix, &mut output);
+            if (self.next_code as usize) < TABLE_SIZE {
+                self.codes.insert((prefix, index), self.next_code);
+                self.next_code += 1;
+                if self.next_code > 1 << self.code_size && self.code_size < MAX_CODE_SIZE {
+                    self.code_size += 1;
+                }
+            } else {
+                self.write_code(clear_code, &mut output);
+                self.reset();
+            }
+            current = Some(index as u16);
+        }
+        if let Some(code) = current {
+            self.write_code(code, &mut output);
+        }
+        let end_code = self.end_code();
+        self.write_code(end_code, &mut output);
+        if self.bit_count > 0 {
+            output.push(self.bits as u8);
+        }
+        Ok(output)
+    }
+}
+
+impl TableBasedImageData<'static> {
+    /// Compresses `indices`, each of which must be lower than `2^lzw_minimum_code_size`, into
+    /// image data split in sub-blocks.
+    pub fn compress(indices: &[u8], lzw_minimum_code_size: u8) -> Result<Self, GifError> {
+        let data = Encoder::new(lzw_minimum_code_size)?.encode(indices)?;
+        debug!(
+            "Compressed {} color indices into {} bytes of image data",
+            indices.len(),
+            data.len()
+        );
+        Ok(Self {
+            lzw_minimum_code_size,
+            image_data: DataSubBlocks::from_data(&data),
+            span: None,
+        })
+    }
+}
+
+impl<'a> TableBasedImageData<'a> {
+    /// Decompresses the image data into exactly `len` color indices.
+    pub fn decompress(&self, len: usize) -> Result<Vec<u8>, GifError> {
+        let mut decoder = Decoder::new(self.lzw_minimum_code_size)?;
+        let mut indices = Vec::new();
+        for block in &self.image_data.blocks {
+            decoder.decode_bytes(&block.data, &mut indices, len)?;
+            if decoder.is_finished() {
+                break;
+            }
+        }
+        if indices.len() < len {
+            return Err(GifError::ImageDataTooShort {
+                offset: decoder.consumed,
+                expected: len,
+                decoded: indices.len(),
+            });
+        }
+        debug!(
+            "Decompressed {} bytes of image data into {} color indices",
+            decoder.consumed,
+            indices.len()
+        );
+        Ok(indices)
+    }
+}
+
+impl<'a> ImageDescriptor<'a> {
+    /// The color indices of the image, `image_width * image_height` of them, row by row from
+    /// top to bottom, even if the image is interlaced.
+    pub fn indices(&self) -> Result<Vec<u8>, GifError> {
+        let indices = self
+            .image_data
+            .decompress(self.image_width as usize * self.image_height as usize)?;
+        if self.interlace_flag() == 1 {
+            Ok(interlace::deinterlace(&indices, self.image_width as usize))
+        } else {
+            Ok(indices)
+        }
+    }
+}
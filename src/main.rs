@@ -3,18 +3,371 @@
 //! v89a: https://www.w3.org/Graphics/GIF/spec-gif89a.txt
 //! v87a: https://www.w3.org/Graphics/GIF/spec-gif87.txt
 
-use giffer::{decoder, Version};
-use std::fs;
+use anyhow::Context;
+use giffer::{
+    assembly,
+    convert::Target,
+    decoder::{self, DecodeOptions},
+    dump,
+    validate::{self, Severity},
+    ApplicationExtension, Block, GifData, GraphicRenderingBlock, Version,
+};
+use std::{
+    env, fs,
+    io::{self, IsTerminal, Read, Write},
+    process::ExitCode,
+};
 
-fn main() -> anyhow::Result<()> {
-    env_logger::init();
+const USAGE: &str = "\
+Usage: giffer <command> [options]
+
+Commands:
+    info <input>                                 Print a summary of a GIF
+    roundtrip <input>                            Check that decoding and encoding a GIF gives back the same bytes
+    validate <input>                             Report spec violations (errors) and risky constructs (warnings)
+    dump [--json] <input>                        Print every block with its offsets and decoded fields, as JSON with --json
+    convert --version <87a|89a|auto> <input> <output>
+                                                 Rewrite a GIF with another version, 'auto' picking the oldest one that can hold it
+    strip-comments <input> <output>              Rewrite a GIF without its comment extensions
+    disassemble <input> <output>                 Describe a GIF block by block in a text format
+    assemble <input> <output>                    Turn a text made by disassemble (or by hand) back into a GIF
+
+<input> and <output> can be '-' for stdin and stdout.
+
+Exit status: 0 on success, 1 on failure (including a failed roundtrip or validation errors), 2 on usage errors.";
+
+enum Command {
+    Info {
+        input: String,
+    },
+    Roundtrip {
+        input: String,
+    },
+    Validate {
+        input: String,
+    },
+    Dump {
+        json: bool,
+        input: String,
+    },
+    Convert {
+        target: Target,
+        input: String,
+        output: String,
+    },
+    StripComments {
+        input: String,
+        output: String,
+    },
+    Disassemble {
+        input: String,
+        output: String,
+    },
+    Assemble {
+        input: String,
+        output: String,
+    },
+    Help,
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Err("missing command".to_string()),
+    };
+    match command {
+        "info" => match args {
+            [input] => Ok(Command::Info {
+                input: input.clone(),
+            }),
+            _ => Err("info expects exactly one <input>".to_string()),
+        },
+        "roundtrip" => match args {
+            [input] => Ok(Command::Roundtrip {
+                input: input.clone(),
+            }),
+            _ => Err("roundtrip expects exactly one <input>".to_string()),
+        },
+        "validate" => match args {
+            [input] => Ok(Command::Validate {
+                input: input.clone(),
+            }),
+            _ => Err("validate expects exactly one <input>".to_string()),
+        },
+        "dump" => match args {
+            [input] => Ok(Command::Dump {
+                json: false,
+                input: input.clone(),
+            }),
+            [flag, input] if flag == "--json" => Ok(Command::Dump {
+                json: true,
+                input: input.clone(),
+            }),
+            _ => Err("dump expects an optional --json and exactly one <input>".to_string()),
+        },
+        "convert" => {
+            let mut target = None;
+            let mut paths = Vec::new();
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--version" => {
+                        target = match args.next().map(String::as_str) {
+                            Some("87a") => Some(Target::V87a),
+                            Some("89a") => Some(Target::V89a),
+                            Some("auto") => Some(Target::Auto),
+                            Some(v) => return Err(format!("unknown version '{}'", v)),
+                            None => return Err("--version expects a value".to_string()),
+                        }
+                    }
+                    _ => paths.push(arg.clone()),
+                }
+            }
+            match (target, paths.as_slice()) {
+                (Some(target), [input, output]) => Ok(Command::Convert {
+                    target,
+                    input: input.clone(),
+                    output: output.clone(),
+                }),
+                (None, _) => Err("convert expects --version".to_string()),
+                _ => Err("convert expects an <input> and an <output>".to_string()),
+            }
+        }
+        "strip-comments" => match args {
+            [input, output] => Ok(Command::StripComments {
+                input: input.clone(),
+                output: output.clone(),
+            }),
+            _ => Err("strip-comments expects an <input> and an <output>".to_string()),
+        },
+        "disassemble" => match args {
+            [input, output] => Ok(Command::Disassemble {
+                input: input.clone(),
+                output: output.clone(),
+            }),
+            _ => Err("disassemble expects an <input> and an <output>".to_string()),
+        },
+        "assemble" => match args {
+            [input, output] => Ok(Command::Assemble {
+                input: input.clone(),
+                output: output.clone(),
+            }),
+            _ => Err("assemble expects an <input> and an <output>".to_string()),
+        },
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        command => Err(format!("unknown command '{}'", command)),
+    }
+}
+
+fn read_input(path: &str) -> anyhow::Result<Vec<u8>> {
+    if path == "-" {
+        let mut data = Vec::new();
+        io::stdin()
+            .read_to_end(&mut data)
+            .context("failed to read stdin")?;
+        Ok(data)
+    } else {
+        fs::read(path).with_context(|| format!("failed to read '{}'", path))
+    }
+}
+
+fn write_output(path: &str, data: &[u8]) -> anyhow::Result<()> {
+    if path == "-" {
+        let mut stdout = io::stdout();
+        stdout.write_all(data).context("failed to write stdout")?;
+        stdout.flush().context("failed to write stdout")
+    } else {
+        fs::write(path, data).with_context(|| format!("failed to write '{}'", path))
+    }
+}
+
+fn decode<'a>(input: &str, data: &'a [u8]) -> anyhow::Result<GifData<'a>> {
+    decoder::decode(data, &DecodeOptions::new())
+        .with_context(|| format!("failed to decode '{}'", input))
+}
+
+fn encode(
+    input: &str,
+    gif: &GifData,
+    version: &Version,
+    discard_comments: bool,
+) -> anyhow::Result<Vec<u8>> {
+    gif.encode(version, discard_comments)
+        .with_context(|| format!("failed to encode '{}'", input))
+}
+
+fn print_info(gif: &GifData) {
+    let lsd = &gif.logical_screen_descriptor;
+    println!("version: {:?}", gif.version);
+    println!(
+        "logical screen: {}x{}",
+        lsd.logical_screen_width, lsd.logical_screen_height
+    );
+    match &lsd.global_color_table {
+        Some(table) => println!("global color table: {} colors", table.len()),
+        None => println!("global color table: none"),
+    }
+    println!("background color index: {}", lsd.background_color_index);
 
-    let path = "/home/agnipau/Downloads/Telegram Desktop/fiero.gif";
-    let orig_data = fs::read(path)?;
-    let parsed_data = decoder::decode(&orig_data, false)?;
-    let data = parsed_data.encode(&Version::V89a, false);
-    assert_eq!(orig_data, data);
-    fs::write("/home/agnipau/Downloads/Telegram Desktop/fiero2.gif", data)?;
+    // Adds up the delays the way `GifData::frames` shows them: a graphic control extension kept
+    // as a block of its own applies to the next image, and plain text isn't rendered.
+    let (mut images, mut plain_texts, mut duration) = (0, 0, 0u64);
+    let mut pending = None;
+    for block in &gif.blocks {
+        match block {
+            Block::GraphicControl(ext) => pending = Some(ext),
+            Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+                images += 1;
+                if let Some(ext) = image.graphic_control_extension.as_ref().or(pending.take()) {
+                    duration += ext.delay_time as u64;
+                }
+            }
+            Block::GraphicRendering(GraphicRenderingBlock::PlainText(_)) => {
+                plain_texts += 1;
+                pending = None;
+            }
+            _ => {}
+        }
+    }
+    println!("images: {}", images);
+    println!("plain text extensions: {}", plain_texts);
+    println!("duration: {}.{:02}s", duration / 100, duration % 100);
+    match gif.loop_count() {
+        Some(0) => println!("loop count: forever"),
+        Some(loop_count) => println!("loop count: {}", loop_count),
+        None => println!("loop count: none"),
+    }
+    let application_extensions: Vec<&ApplicationExtension> = gif.application_extensions().collect();
+    println!("application extensions: {}", application_extensions.len());
+    for ext in application_extensions {
+        println!(
+            "    {}{}",
+            String::from_utf8_lossy(&ext.identifier),
+            String::from_utf8_lossy(&ext.authentication_code)
+        );
+    }
+    println!("comment extensions: {}", gif.comment_extensions().count());
+    let unknown_extensions = gif
+        .blocks
+        .iter()
+        .filter(|block| matches!(block, Block::Unknown { .. }))
+        .count();
+    println!("unknown extensions: {}", unknown_extensions);
+}
+
+fn run(command: Command) -> anyhow::Result<ExitCode> {
+    match command {
+        Command::Info { input } => {
+            let data = read_input(&input)?;
+            print_info(&decode(&input, &data)?);
+        }
+        Command::Roundtrip { input } => {
+            let data = read_input(&input)?;
+            let gif = decode(&input, &data)?;
+            let encoded = encode(&input, &gif, &gif.version, false)?;
+            if encoded != data {
+                let offset = encoded
+                    .iter()
+                    .zip(&data)
+                    .position(|(a, b)| a != b)
+                    .unwrap_or_else(|| encoded.len().min(data.len()));
+                eprintln!(
+                    "giffer: '{}' doesn't round-trip: {} bytes read, {} bytes written, first difference at offset {}",
+                    input,
+                    data.len(),
+                    encoded.len(),
+                    offset
+                );
+                return Ok(ExitCode::FAILURE);
+            }
+            println!("'{}' round-trips ({} bytes)", input, data.len());
+        }
+        Command::Validate { input } => {
+            let data = read_input(&input)?;
+            let diagnostics = validate::validate_bytes(&data)
+                .with_context(|| format!("failed to decode '{}'", input))?;
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+            let errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity() == Severity::Error)
+                .count();
+            println!(
+                "'{}': {} error(s), {} warning(s)",
+                input,
+                errors,
+                diagnostics.len() - errors
+            );
+            if errors > 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Dump { json, input } => {
+            let data = read_input(&input)?;
+            let gif = decode(&input, &data)?;
+            let dump = if json {
+                dump::json(&gif) + "\n"
+            } else {
+                dump::text(&gif, io::stdout().is_terminal())
+            };
+            write_output("-", dump.as_bytes())?;
+        }
+        Command::Convert {
+            target,
+            input,
+            output,
+        } => {
+            let data = read_input(&input)?;
+            let gif = decode(&input, &data)?;
+            let conversion = gif
+                .convert(target)
+                .with_context(|| format!("failed to convert '{}'", input))?;
+            for loss in &conversion.losses {
+                eprintln!("giffer: warning: {}", loss);
+            }
+            let gif = conversion.gif;
+            write_output(&output, &encode(&input, &gif, &gif.version, false)?)?;
+        }
+        Command::StripComments { input, output } => {
+            let data = read_input(&input)?;
+            let gif = decode(&input, &data)?;
+            write_output(&output, &encode(&input, &gif, &gif.version, true)?)?;
+        }
+        Command::Disassemble { input, output } => {
+            let data = read_input(&input)?;
+            let gif = decode(&input, &data)?;
+            write_output(&output, assembly::disassemble(&gif).as_bytes())?;
+        }
+        Command::Assemble { input, output } => {
+            let data = read_input(&input)?;
+            let text = String::from_utf8(data)
+                .with_context(|| format!("failed to assemble '{}'", input))?;
+            let gif = assembly::assemble(&text)
+                .with_context(|| format!("failed to assemble '{}'", input))?;
+            write_output(&output, &gif)?;
+        }
+        Command::Help => println!("{}", USAGE),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
 
-    Ok(())
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("giffer: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(command) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("giffer: error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
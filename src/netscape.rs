@@ -0,0 +1,115 @@
+//! The NETSCAPE2.0 application extension (and its ANIMEXTS1.0 alias), which tells viewers how
+//! many times to loop an animation and how much of the data stream to buffer before playing it.
+//!
+//! Its data is a sequence of sub-blocks whose first byte is an ID: `1` is followed by the loop
+//! count (`u16`), `2` by the buffering size (`u32`), both little-endian.
+
+use crate::{ApplicationExtension, Block, DataSubBlock, DataSubBlocks, GifData};
+use std::borrow::Cow;
+
+const NETSCAPE: (&[u8], &[u8]) = (b"NETSCAPE", b"2.0");
+const ANIMEXTS: (&[u8], &[u8]) = (b"ANIMEXTS", b"1.0");
+
+const LOOP_COUNT_ID: u8 = 1;
+const BUFFERING_SIZE_ID: u8 = 2;
+
+/// The typed contents of a NETSCAPE2.0 or ANIMEXTS1.0 application extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetscapeExtension {
+    /// How many times the animation should be repeated, 0 meaning forever.
+    pub loop_count: Option<u16>,
+    /// How many bytes should be buffered before starting to play the animation.
+    pub buffering_size: Option<u32>,
+}
+
+impl<'a> ApplicationExtension<'a> {
+    /// Creates a NETSCAPE2.0 extension holding just a loop count.
+    pub fn looping(loop_count: u16) -> ApplicationExtension<'static> {
+        ApplicationExtension {
+            identifier: Cow::Borrowed(NETSCAPE.0),
+            authentication_code: Cow::Borrowed(NETSCAPE.1),
+            data: DataSubBlocks {
+                blocks: vec![loop_count_sub_block(loop_count)],
+            },
+            span: None,
+        }
+    }
+
+    pub fn is_netscape(&self) -> bool {
+        let id = (&*self.identifier, &*self.authentication_code);
+        id == NETSCAPE || id == ANIMEXTS
+    }
+
+    /// Parses the data of a NETSCAPE2.0 or ANIMEXTS1.0 extension. Returns `None` for any other
+    /// extension, or if a sub-block isn't one of the two known ones, in which case only the raw
+    /// [`ApplicationExtension::data`] is meaningful.
+    pub fn netscape(&self) -> Option<NetscapeExtension> {
+        if !self.is_netscape() {
+            return None;
+        }
+        let mut ext = NetscapeExtension {
+            loop_count: None,
+            buffering_size: None,
+        };
+        for block in &self.data.blocks {
+            match *block.data {
+                [LOOP_COUNT_ID, a, b] => ext.loop_count = Some(u16::from_le_bytes([a, b])),
+                [BUFFERING_SIZE_ID, a, b, c, d] => {
+                    ext.buffering_size = Some(u32::from_le_bytes([a, b, c, d]))
+                }
+                _ => return None,
+            }
+        }
+        Some(ext)
+    }
+
+    /// Sets the loop count of a NETSCAPE2.0 or ANIMEXTS1.0 extension, editing the loop count
+    /// sub-block or adding one in front of the others. The other sub-blocks are left untouched.
+    pub fn set_loop_count(&mut self, loop_count: u16) {
+        let block = loop_count_sub_block(loop_count);
+        match self
+            .data
+            .blocks
+            .iter_mut()
+            .find(|block| block.data.len() == 3 && block.data[0] == LOOP_COUNT_ID)
+        {
+            Some(existing) => *existing = block,
+            None => self.data.blocks.insert(0, block),
+        }
+    }
+}
+
+fn loop_count_sub_block(loop_count: u16) -> DataSubBlock<'static> {
+    let [a, b] = loop_count.to_le_bytes();
+    DataSubBlock {
+        block_size: 3,
+        data: Cow::Owned(vec![LOOP_COUNT_ID, a, b]),
+        span: None,
+    }
+}
+
+impl<'a> GifData<'a> {
+    /// The loop count of the first NETSCAPE2.0 or ANIMEXTS1.0 extension, 0 meaning forever.
+    /// `None` if there's no such extension, in which case viewers play the animation once.
+    pub fn loop_count(&self) -> Option<u16> {
+        self.application_extensions()
+            .filter_map(ApplicationExtension::netscape)
+            .find_map(|ext| ext.loop_count)
+    }
+
+    /// Sets the loop count in the first NETSCAPE2.0 or ANIMEXTS1.0 extension, inserting a
+    /// NETSCAPE2.0 extension as the first block if there's none.
+    pub fn set_loop_count(&mut self, loop_count: u16) {
+        let ext = self.blocks.iter_mut().find_map(|block| match block {
+            Block::Application(ext) if ext.is_netscape() => Some(ext),
+            _ => None,
+        });
+        match ext {
+            Some(ext) => ext.set_loop_count(loop_count),
+            None => self.blocks.insert(
+                0,
+                Block::Application(ApplicationExtension::looping(loop_count)),
+            ),
+        }
+    }
+}
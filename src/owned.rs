@@ -0,0 +1,174 @@
+//! Conversions of borrowed blocks into blocks that own their data.
+
+use crate::{
+    ApplicationExtension, Block, ColorTable, CommentExtension, DataSubBlock, DataSubBlocks,
+    ExtensionBlock, GifData, GraphicRenderingBlock, ImageDescriptor, LogicalScreenDescriptor,
+    PlainTextExtension, TableBasedImageData,
+};
+use std::borrow::Cow;
+
+fn into_owned(data: Cow<[u8]>) -> Cow<'static, [u8]> {
+    Cow::Owned(data.into_owned())
+}
+
+impl<'a> GifData<'a> {
+    /// Copies everything borrowed from the decoded bytes, so that the result can outlive them.
+    pub fn into_owned(self) -> GifData<'static> {
+        GifData {
+            version: self.version,
+            logical_screen_descriptor: self.logical_screen_descriptor.into_owned(),
+            blocks: self.blocks.into_iter().map(Block::into_owned).collect(),
+            trailer: self.trailer,
+        }
+    }
+}
+
+impl<'a> Block<'a> {
+    pub fn into_owned(self) -> Block<'static> {
+        match self {
+            Self::Application(ext) => Block::Application(ext.into_owned()),
+            Self::Comment(ext) => Block::Comment(ext.into_owned()),
+            Self::GraphicRendering(block) => Block::GraphicRendering(block.into_owned()),
+            Self::GraphicControl(ext) => Block::GraphicControl(ext),
+            Self::Unknown { label, data, span } => Block::Unknown {
+                label,
+                data: data.into_owned(),
+                span,
+            },
+        }
+    }
+}
+
+impl<'a> ExtensionBlock<'a> {
+    pub(crate) fn into_owned(self) -> ExtensionBlock<'static> {
+        match self {
+            Self::GraphicControl(ext) => ExtensionBlock::GraphicControl(ext),
+            Self::Comment(ext) => ExtensionBlock::Comment(ext.into_owned()),
+            Self::PlainText(ext) => ExtensionBlock::PlainText(ext.into_owned()),
+            Self::Application(ext) => ExtensionBlock::Application(ext.into_owned()),
+            Self::Unknown { label, data, span } => ExtensionBlock::Unknown {
+                label,
+                data: data.into_owned(),
+                span,
+            },
+        }
+    }
+}
+
+impl<'a> GraphicRenderingBlock<'a> {
+    pub fn into_owned(self) -> GraphicRenderingBlock<'static> {
+        match self {
+            Self::PlainText(ext) => GraphicRenderingBlock::PlainText(ext.into_owned()),
+            Self::Image(image) => GraphicRenderingBlock::Image(image.into_owned()),
+        }
+    }
+}
+
+impl<'a> LogicalScreenDescriptor<'a> {
+    pub fn into_owned(self) -> LogicalScreenDescriptor<'static> {
+        LogicalScreenDescriptor {
+            logical_screen_width: self.logical_screen_width,
+            logical_screen_height: self.logical_screen_height,
+            packed_fields: self.packed_fields,
+            background_color_index: self.background_color_index,
+            pixel_aspect_ratio: self.pixel_aspect_ratio,
+            global_color_table: self.global_color_table.map(ColorTable::into_owned),
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> ColorTable<'a> {
+    pub fn into_owned(self) -> ColorTable<'static> {
+        ColorTable {
+            pixels: into_owned(self.pixels),
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> DataSubBlock<'a> {
+    pub fn into_owned(self) -> DataSubBlock<'static> {
+        DataSubBlock {
+            block_size: self.block_size,
+            data: into_owned(self.data),
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> DataSubBlocks<'a> {
+    pub fn into_owned(self) -> DataSubBlocks<'static> {
+        DataSubBlocks {
+            blocks: self
+                .blocks
+                .into_iter()
+                .map(DataSubBlock::into_owned)
+                .collect(),
+        }
+    }
+}
+
+impl<'a> ApplicationExtension<'a> {
+    pub fn into_owned(self) -> ApplicationExtension<'static> {
+        ApplicationExtension {
+            identifier: into_owned(self.identifier),
+            authentication_code: into_owned(self.authentication_code),
+            data: self.data.into_owned(),
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> PlainTextExtension<'a> {
+    pub fn into_owned(self) -> PlainTextExtension<'static> {
+        PlainTextExtension {
+            text_grid_left_position: self.text_grid_left_position,
+            text_grid_top_position: self.text_grid_top_position,
+            text_grid_width: self.text_grid_width,
+            text_grid_height: self.text_grid_height,
+            character_cell_width: self.character_cell_width,
+            character_cell_height: self.character_cell_height,
+            text_foreground_color_index: self.text_foreground_color_index,
+            text_background_color_index: self.text_background_color_index,
+            data: self.data.into_owned(),
+            graphic_control_extension: self.graphic_control_extension,
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> CommentExtension<'a> {
+    pub fn into_owned(self) -> CommentExtension<'static> {
+        CommentExtension {
+            data: self.data.into_owned(),
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> ImageDescriptor<'a> {
+    pub fn into_owned(self) -> ImageDescriptor<'static> {
+        ImageDescriptor {
+            image_left_position: self.image_left_position,
+            image_top_position: self.image_top_position,
+            image_width: self.image_width,
+            image_height: self.image_height,
+            packed_fields: self.packed_fields,
+            local_color_table: self.local_color_table.map(ColorTable::into_owned),
+            image_data: self.image_data.into_owned(),
+            graphic_control_extension: self.graphic_control_extension,
+            span: self.span,
+        }
+    }
+}
+
+impl<'a> TableBasedImageData<'a> {
+    pub fn into_owned(self) -> TableBasedImageData<'static> {
+        TableBasedImageData {
+            lzw_minimum_code_size: self.lzw_minimum_code_size,
+            image_data: self.image_data.into_owned(),
+            span: self.span,
+        }
+    }
+}
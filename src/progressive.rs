@@ -0,0 +1,113 @@
+//! Row by row decompression of image data, for viewers that want to display an image before all
+//! of its data has been received or decompressed.
+
+use crate::{interlace, lzw, GifError, ImageDescriptor};
+
+/// A row of color indices that has just become available.
+#[derive(Debug, Clone, Copy)]
+pub struct Row<'r> {
+    /// The row of the image, counted from the top, which for interlaced images isn't the order
+    /// rows are reported in.
+    pub y: usize,
+    /// The interlace pass the row belongs to, from 1 to 4. Always 1 for images that aren't
+    /// interlaced.
+    pub pass: u8,
+    /// How many rows, starting from `y`, a coarse preview can fill with this row until the later
+    /// passes provide them. Always 1 for images that aren't interlaced.
+    pub height: usize,
+    pub indices: &'r [u8],
+}
+
+/// Decompresses the image data of an image fed to it a piece at a time, reporting every row as
+/// soon as all of its color indices are known.
+pub struct RowDecoder {
+    decoder: lzw::Decoder,
+    width: usize,
+    /// (y, pass, height) of every row, in storage order.
+    rows: Vec<(usize, u8, usize)>,
+    indices: Vec<u8>,
+    reported: usize,
+}
+
+impl RowDecoder {
+    /// Prepares to decompress the image data of `image`, whose own image data is ignored.
+    pub fn new(image: &ImageDescriptor) -> Result<Self, GifError> {
+        let width = image.image_width as usize;
+        let height = image.image_height as usize;
+        let rows = if image.interlace_flag() == 1 {
+            interlace::PASSES
+                .iter()
+                .zip(1..)
+                .flat_map(|(&(first, step, preview), pass)| {
+                    (first..height)
+                        .step_by(step)
+                        .map(move |y| (y, pass, preview.min(height - y)))
+                })
+                .collect()
+        } else {
+            (0..height).map(|y| (y, 1, 1)).collect()
+        };
+        Ok(Self {
+            decoder: lzw::Decoder::new(image.image_data.lzw_minimum_code_size)?,
+            width,
+            rows,
+            indices: Vec::new(),
+            reported: 0,
+        })
+    }
+
+    /// Decompresses the payload of the next sub-blocks of image data, calling `on_row` for every
+    /// row completed by it.
+    pub fn feed(&mut self, data: &[u8], mut on_row: impl FnMut(Row<'_>)) -> Result<(), GifError> {
+        let max_len = self.width * self.rows.len();
+        self.decoder
+            .decode_bytes(data, &mut self.indices, max_len)?;
+        while self.reported < self.rows.len()
+            && (self.reported + 1) * self.width <= self.indices.len()
+        {
+            let (y, pass, height) = self.rows[self.reported];
+            let start = self.reported * self.width;
+            on_row(Row {
+                y,
+                pass,
+                height,
+                indices: &self.indices[start..start + self.width],
+            });
+            self.reported += 1;
+        }
+        Ok(())
+    }
+
+    /// How many rows have been reported so far.
+    pub fn rows_decoded(&self) -> usize {
+        self.reported
+    }
+
+    /// Whether every row of the image has been reported.
+    pub fn is_finished(&self) -> bool {
+        self.reported == self.rows.len()
+    }
+}
+
+impl<'a> ImageDescriptor<'a> {
+    /// Decompresses the image data one sub-block at a time, calling `on_row` for every row as soon
+    /// as it's complete. Rows are reported in storage order, so interlaced images are reported
+    /// one interlace pass after the other.
+    pub fn decode_rows(&self, mut on_row: impl FnMut(Row<'_>)) -> Result<(), GifError> {
+        let mut decoder = RowDecoder::new(self)?;
+        for block in &self.image_data.image_data.blocks {
+            decoder.feed(&block.data, &mut on_row)?;
+            if decoder.is_finished() {
+                break;
+            }
+        }
+        if !decoder.is_finished() {
+            return Err(GifError::ImageDataTooShort {
+                offset: decoder.decoder.consumed(),
+                expected: decoder.width * decoder.rows.len(),
+                decoded: decoder.indices.len(),
+            });
+        }
+        Ok(())
+    }
+}
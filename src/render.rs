@@ -0,0 +1,236 @@
+//! Compositing of the images of a GIF onto a logical screen sized canvas, the way a viewer would
+//! display them.
+
+use crate::{
+    decoder::{DecodeOptions, Limit},
+    Block, ColorTable, DisposalMethod, GifData, GifError, GraphicControlExtension,
+    GraphicRenderingBlock, ImageDescriptor,
+};
+use log::{debug, info};
+
+/// The most pixels a canvas can have unless set otherwise with
+/// [`DecodeOptions::max_canvas_pixels`], 64 MiB of them, taking 256 MiB as RGBA. The logical
+/// screen can declare up to 65535x65535 pixels, which would take 16 GiB.
+pub const MAX_CANVAS_PIXELS: u64 = 1 << 26;
+
+/// A fully composited frame.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub width: u16,
+    pub height: u16,
+    /// RGBA, row by row, `width * height * 4` bytes.
+    pub pixels: Vec<u8>,
+    /// In hundredths of a second.
+    pub delay_time: u16,
+}
+
+/// What to do with the area of an image once it has been displayed, before the next one.
+struct Disposal {
+    method: DisposalMethod,
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+    /// The canvas as it was before the image was drawn, for "restore to previous".
+    previous: Option<Vec<u8>>,
+}
+
+/// Iterator over the composited frames of a GIF, see [`GifData::frames`].
+pub struct Frames<'g, 'a> {
+    images: Box<
+        dyn Iterator<Item = (&'g ImageDescriptor<'a>, Option<&'g GraphicControlExtension>)> + 'g,
+    >,
+    global_color_table: Option<&'g ColorTable<'a>>,
+    width: usize,
+    height: usize,
+    background: [u8; 4],
+    /// Empty until the first image is drawn.
+    canvas: Vec<u8>,
+    disposal: Option<Disposal>,
+    /// Where the logical screen descriptor is, for errors about the canvas.
+    offset: usize,
+    max_canvas_pixels: u64,
+}
+
+impl<'a> GifData<'a> {
+    /// Composites every image onto the logical screen, honouring positions, transparency and
+    /// disposal methods.
+    ///
+    /// The background is the global color table entry at `background_color_index`, or
+    /// transparent black if there's no such entry. Color indices outside of the active color
+    /// table are treated as transparent. Plain text extensions aren't rendered. A graphic control
+    /// extension kept as a block of its own applies to the next image without one.
+    ///
+    /// The canvas is as large as the logical screen, up to 16 GiB for 65535x65535 pixels. It's
+    /// allocated when drawing the first image, which fails with [`GifError::LimitExceeded`] if
+    /// the logical screen has more than [`MAX_CANVAS_PIXELS`] pixels, see
+    /// [`GifData::frames_with_options`] to set another limit.
+    pub fn frames(&self) -> Frames<'_, 'a> {
+        self.frames_with_options(&DecodeOptions::new())
+    }
+
+    /// Like [`GifData::frames`], with the canvas limited by
+    /// [`DecodeOptions::max_canvas_pixels`]. Passing the options the data stream was decoded
+    /// with rejects a logical screen that's too large before decoding any of the rest.
+    pub fn frames_with_options(&self, options: &DecodeOptions) -> Frames<'_, 'a> {
+        let lsd = &self.logical_screen_descriptor;
+        let background = lsd
+            .global_color_table
+            .as_ref()
+            .and_then(|table| rgba(table, lsd.background_color_index))
+            .unwrap_or([0, 0, 0, 0]);
+        let width = lsd.logical_screen_width as usize;
+        let height = lsd.logical_screen_height as usize;
+        Frames {
+            images: Box::new(self.blocks.iter().filter_map({
+                let mut pending = None;
+                move |block| match block {
+                    Block::GraphicControl(ext) => {
+                        pending = Some(ext);
+                        None
+                    }
+                    Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => Some((
+                        image,
+                        image.graphic_control_extension.as_ref().or(pending.take()),
+                    )),
+                    Block::GraphicRendering(GraphicRenderingBlock::PlainText(_)) => {
+                        info!("Skipping plain text extension, it can't be rendered");
+                        pending = None;
+                        None
+                    }
+                    _ => None,
+                }
+            })),
+            global_color_table: lsd.global_color_table.as_ref(),
+            width,
+            height,
+            background,
+            canvas: Vec::new(),
+            disposal: None,
+            offset: lsd
+                .span
+                .map_or(crate::SIGNATURE.len() + 3, |span| span.start),
+            max_canvas_pixels: options.canvas_pixels_limit(),
+        }
+    }
+}
+
+fn rgba(table: &ColorTable, idx: u8) -> Option<[u8; 4]> {
+    let idx = idx as usize;
+    if idx * 3 + 3 > table.pixels.len() {
+        return None;
+    }
+    let pixel = table.get_pixel(idx);
+    Some([pixel[0], pixel[1], pixel[2], 0xff])
+}
+
+impl<'g, 'a> Frames<'g, 'a> {
+    fn dispose(&mut self) {
+        let disposal = match self.disposal.take() {
+            Some(disposal) => disposal,
+            None => return,
+        };
+        match disposal.method {
+            DisposalMethod::RestoreBackground => {
+                debug!("Restoring the area of the previous image to the background");
+                for y in disposal.top..(disposal.top + disposal.height).min(self.height) {
+                    for x in disposal.left..(disposal.left + disposal.width).min(self.width) {
+                        let i = (y * self.width + x) * 4;
+                        self.canvas[i..i + 4].copy_from_slice(&self.background);
+                    }
+                }
+            }
+            DisposalMethod::RestorePrevious => {
+                debug!("Restoring the canvas to its state before the previous image");
+                if let Some(previous) = disposal.previous {
+                    self.canvas = previous;
+                }
+            }
+            DisposalMethod::Unspecified | DisposalMethod::None | DisposalMethod::Reserved(_) => {}
+        }
+    }
+
+    fn draw(
+        &mut self,
+        image: &ImageDescriptor<'a>,
+        graphic_control_extension: Option<&GraphicControlExtension>,
+    ) -> Result<Frame, GifError> {
+        if self.canvas.is_empty() {
+            let pixels = self.width as u64 * self.height as u64;
+            if pixels > self.max_canvas_pixels {
+                return Err(GifError::LimitExceeded {
+                    offset: self.offset,
+                    limit: Limit::CanvasPixels,
+                    value: pixels,
+                    max: self.max_canvas_pixels,
+                });
+            }
+            self.canvas = self.background.repeat(pixels as usize);
+        }
+        self.dispose();
+
+        let indices = image.indices()?;
+        let color_table = image.local_color_table.as_ref().or(self.global_color_table);
+        let transparent_color_index = graphic_control_extension
+            .filter(|ext| ext.transparent_color_flag() == 1)
+            .map(|ext| ext.transparent_color_index);
+        let disposal_method = graphic_control_extension
+            .map(|ext| ext.disposal_method())
+            .unwrap_or(DisposalMethod::Unspecified);
+
+        let left = image.image_left_position as usize;
+        let top = image.image_top_position as usize;
+        let image_width = image.image_width as usize;
+        let image_height = image.image_height as usize;
+        self.disposal = Some(Disposal {
+            method: disposal_method,
+            left,
+            top,
+            width: image_width,
+            height: image_height,
+            previous: if disposal_method == DisposalMethod::RestorePrevious {
+                Some(self.canvas.clone())
+            } else {
+                None
+            },
+        });
+
+        for (row, row_indices) in indices.chunks(image_width.max(1)).enumerate() {
+            let y = top + row;
+            if y >= self.height {
+                break;
+            }
+            for (column, &idx) in row_indices.iter().enumerate() {
+                let x = left + column;
+                if x >= self.width {
+                    break;
+                }
+                if Some(idx) == transparent_color_index {
+                    continue;
+                }
+                if let Some(pixel) = color_table.and_then(|table| rgba(table, idx)) {
+                    let i = (y * self.width + x) * 4;
+                    self.canvas[i..i + 4].copy_from_slice(&pixel);
+                }
+            }
+        }
+
+        Ok(Frame {
+            width: self.width as u16,
+            height: self.height as u16,
+            pixels: self.canvas.clone(),
+            delay_time: graphic_control_extension
+                .map(|ext| ext.delay_time)
+                .unwrap_or(0),
+        })
+    }
+}
+
+impl<'g, 'a> Iterator for Frames<'g, 'a> {
+    type Item = Result<Frame, GifError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (image, graphic_control_extension) = self.images.next()?;
+        Some(self.draw(image, graphic_control_extension))
+    }
+}
@@ -0,0 +1,377 @@
+//! Checks for spec violations and for constructs that are valid but that viewers are known to
+//! handle inconsistently.
+
+use crate::{
+    decoder::{self, DecodeOptions},
+    Block, ColorTable, DataSubBlock, DisposalMethod, GifData, GifError, GraphicControlExtension,
+    GraphicRenderingBlock, ImageDescriptor, Span, Version,
+};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Valid, but likely to be displayed differently by different viewers.
+    Warning,
+    /// Not allowed by the spec.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// Something wrong with a GIF, found by [`validate`] or [`validate_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The color table flag and size in the packed fields describe a table of `declared` colors,
+    /// while the color table has `actual` colors. `None` means there's no table.
+    ColorTableMismatch {
+        global: bool,
+        declared: Option<usize>,
+        actual: Option<usize>,
+    },
+    /// The background color index isn't in the global color table.
+    BackgroundColorOutOfRange { index: u8, colors: usize },
+    /// The transparent color index isn't in the color table of the image.
+    TransparentColorOutOfRange { index: u8, colors: usize },
+    /// An image doesn't fit in the logical screen.
+    ImageOutsideLogicalScreen {
+        right: u32,
+        bottom: u32,
+        logical_screen_width: u16,
+        logical_screen_height: u16,
+    },
+    /// An extension in a GIF87a data stream, which only GIF89a defines.
+    ExtensionIn87a { extension: &'static str },
+    /// A disposal method from 4 to 7.
+    ReservedDisposalMethod { value: u8 },
+    /// A plain text extension in a data stream without a global color table, leaving its text
+    /// colors undefined.
+    PlainTextWithoutGlobalColorTable,
+    /// An image of an animation with a delay of 0, or without a graphic control extension to set
+    /// one, which most viewers replace with their own minimum delay.
+    ZeroDelay,
+    /// `count` sub-blocks of image data are shorter than 255 bytes without being the last one.
+    ShortSubBlocks { count: usize },
+    /// Bytes following the trailer.
+    TrailingData { len: usize },
+}
+
+impl Problem {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::ColorTableMismatch { .. }
+            | Self::ImageOutsideLogicalScreen { .. }
+            | Self::ExtensionIn87a { .. }
+            | Self::ReservedDisposalMethod { .. } => Severity::Error,
+            Self::BackgroundColorOutOfRange { .. }
+            | Self::TransparentColorOutOfRange { .. }
+            | Self::PlainTextWithoutGlobalColorTable
+            | Self::ZeroDelay
+            | Self::ShortSubBlocks { .. }
+            | Self::TrailingData { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ColorTableMismatch {
+                global,
+                declared,
+                actual,
+            } => {
+                let colors = |colors: &Option<usize>| match colors {
+                    Some(colors) => format!("{} colors", colors),
+                    None => "none".to_string(),
+                };
+                write!(
+                    f,
+                    "{} color table mismatch: {} declared by the packed fields, {} found",
+                    if *global { "global" } else { "local" },
+                    colors(declared),
+                    colors(actual)
+                )
+            }
+            Self::BackgroundColorOutOfRange { index, colors } => write!(
+                f,
+                "background color index {} is outside of the global color table of {} colors",
+                index, colors
+            ),
+            Self::TransparentColorOutOfRange { index, colors } => write!(
+                f,
+                "transparent color index {} is outside of the color table of {} colors",
+                index, colors
+            ),
+            Self::ImageOutsideLogicalScreen {
+                right,
+                bottom,
+                logical_screen_width,
+                logical_screen_height,
+            } => write!(
+                f,
+                "image extends to {}x{}, beyond the {}x{} logical screen",
+                right, bottom, logical_screen_width, logical_screen_height
+            ),
+            Self::ExtensionIn87a { extension } => {
+                write!(f, "{} extension in a GIF87a data stream", extension)
+            }
+            Self::ReservedDisposalMethod { value } => {
+                write!(f, "reserved disposal method {}", value)
+            }
+            Self::PlainTextWithoutGlobalColorTable => {
+                f.write_str("plain text extension without a global color table")
+            }
+            Self::ZeroDelay => f.write_str("image of an animation with a delay of 0"),
+            Self::ShortSubBlocks { count } => write!(
+                f,
+                "{} sub-block(s) of image data shorter than 255 bytes before the last one",
+                count
+            ),
+            Self::TrailingData { len } => write!(f, "{} byte(s) after the trailer", len),
+        }
+    }
+}
+
+/// A [`Problem`] and where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The offset of the block the problem was found in, `None` for blocks that weren't decoded.
+    pub offset: Option<usize>,
+    pub problem: Problem,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        self.problem.severity()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.severity())?;
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        write!(f, ": {}", self.problem)
+    }
+}
+
+/// Decodes `bytes` and validates the result, also looking for data after the trailer.
+pub fn validate_bytes(bytes: &[u8]) -> Result<Vec<Diagnostic>, GifError> {
+    let (gif, _, end) = decoder::decode_data_stream(bytes, &DecodeOptions::new())?;
+    let mut diagnostics = validate(&gif);
+    if end < bytes.len() {
+        diagnostics.push(Diagnostic {
+            offset: Some(end),
+            problem: Problem::TrailingData {
+                len: bytes.len() - end,
+            },
+        });
+    }
+    Ok(diagnostics)
+}
+
+/// Looks for problems in `gif`, in data stream order.
+pub fn validate(gif: &GifData) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        gif,
+        diagnostics: Vec::new(),
+    };
+    validator.validate();
+    validator.diagnostics
+}
+
+struct Validator<'g, 'a> {
+    gif: &'g GifData<'a>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'g, 'a> Validator<'g, 'a> {
+    fn report(&mut self, span: Option<Span>, problem: Problem) {
+        self.diagnostics.push(Diagnostic {
+            offset: span.map(|span| span.start),
+            problem,
+        });
+    }
+
+    fn validate(&mut self) {
+        let lsd = &self.gif.logical_screen_descriptor;
+        self.check_color_table(
+            lsd.span,
+            true,
+            lsd.global_color_table_flag(),
+            lsd.global_color_table_size(),
+            lsd.global_color_table.as_ref(),
+        );
+        if let Some(table) = &lsd.global_color_table {
+            if lsd.background_color_index as usize >= table.len() {
+                self.report(
+                    lsd.span,
+                    Problem::BackgroundColorOutOfRange {
+                        index: lsd.background_color_index,
+                        colors: table.len(),
+                    },
+                );
+            }
+        }
+
+        let animated = self
+            .gif
+            .graphic_rendering_blocks()
+            .filter(|block| matches!(block, GraphicRenderingBlock::Image(_)))
+            .count()
+            > 1;
+        // A graphic control extension kept as a block of its own, which applies to the next image
+        // the way rendering does.
+        let mut pending = None;
+        for block in &self.gif.blocks {
+            match block {
+                Block::Application(ext) => self.check_extension(ext.span, "application"),
+                Block::Comment(ext) => self.check_extension(ext.span, "comment"),
+                Block::GraphicControl(ext) => {
+                    self.check_graphic_control_extension(ext);
+                    pending = Some(ext);
+                }
+                Block::Unknown { .. } => {}
+                Block::GraphicRendering(GraphicRenderingBlock::PlainText(ext)) => {
+                    pending = None;
+                    if let Some(gce) = &ext.graphic_control_extension {
+                        self.check_graphic_control_extension(gce);
+                    }
+                    self.check_extension(ext.span, "plain text");
+                    if self
+                        .gif
+                        .logical_screen_descriptor
+                        .global_color_table
+                        .is_none()
+                    {
+                        self.report(ext.span, Problem::PlainTextWithoutGlobalColorTable);
+                    }
+                }
+                Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+                    let gce = image.graphic_control_extension.as_ref().or(pending.take());
+                    self.check_image(image, gce, animated)
+                }
+            }
+        }
+    }
+
+    fn check_color_table(
+        &mut self,
+        span: Option<Span>,
+        global: bool,
+        flag: u8,
+        size_bits: u8,
+        table: Option<&ColorTable>,
+    ) {
+        let declared = crate::color_table_len(flag, size_bits);
+        let actual = table.map(ColorTable::len);
+        if declared != actual {
+            self.report(
+                span,
+                Problem::ColorTableMismatch {
+                    global,
+                    declared,
+                    actual,
+                },
+            );
+        }
+    }
+
+    fn check_extension(&mut self, span: Option<Span>, extension: &'static str) {
+        if self.gif.version == Version::V87a {
+            self.report(span, Problem::ExtensionIn87a { extension });
+        }
+    }
+
+    fn check_graphic_control_extension(&mut self, ext: &GraphicControlExtension) {
+        self.check_extension(ext.span, "graphic control");
+        if let DisposalMethod::Reserved(value) = ext.disposal_method() {
+            self.report(ext.span, Problem::ReservedDisposalMethod { value });
+        }
+    }
+
+    /// `graphic_control_extension` is the one applying to the image, either its own or the one
+    /// kept as a block of its own before it.
+    fn check_image(
+        &mut self,
+        image: &ImageDescriptor,
+        graphic_control_extension: Option<&GraphicControlExtension>,
+        animated: bool,
+    ) {
+        let lsd = &self.gif.logical_screen_descriptor;
+        self.check_color_table(
+            image.span,
+            false,
+            image.local_color_table_flag(),
+            image.local_color_table_size(),
+            image.local_color_table.as_ref(),
+        );
+
+        let right = image.image_left_position as u32 + image.image_width as u32;
+        let bottom = image.image_top_position as u32 + image.image_height as u32;
+        if right > lsd.logical_screen_width as u32 || bottom > lsd.logical_screen_height as u32 {
+            self.report(
+                image.span,
+                Problem::ImageOutsideLogicalScreen {
+                    right,
+                    bottom,
+                    logical_screen_width: lsd.logical_screen_width,
+                    logical_screen_height: lsd.logical_screen_height,
+                },
+            );
+        }
+
+        if let Some(gce) = &image.graphic_control_extension {
+            self.check_graphic_control_extension(gce);
+        }
+        if let Some(gce) = graphic_control_extension {
+            let table = image
+                .local_color_table
+                .as_ref()
+                .or(lsd.global_color_table.as_ref());
+            if let Some(table) = table {
+                if gce.transparent_color_flag() == 1
+                    && gce.transparent_color_index as usize >= table.len()
+                {
+                    self.report(
+                        gce.span,
+                        Problem::TransparentColorOutOfRange {
+                            index: gce.transparent_color_index,
+                            colors: table.len(),
+                        },
+                    );
+                }
+            }
+        }
+        if animated {
+            match graphic_control_extension {
+                Some(gce) if gce.delay_time == 0 => self.report(gce.span, Problem::ZeroDelay),
+                // Without a graphic control extension there's no delay either.
+                None => self.report(image.span, Problem::ZeroDelay),
+                Some(_) => {}
+            }
+        }
+
+        let blocks = &image.image_data.image_data.blocks;
+        let short = blocks
+            .iter()
+            .rev()
+            .skip(1)
+            .filter(|block| block.data.len() < DataSubBlock::MAX_SIZE)
+            .count();
+        if short > 0 {
+            self.report(
+                image.image_data.span,
+                Problem::ShortSubBlocks { count: short },
+            );
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use giffer::{
+    assembly::{self, AssembleError},
+    decoder::{self, DecodeOptions},
+    Block, DisposalMethod, GraphicRenderingBlock, ImageDescriptor,
+};
+
+fn images<'g, 'a>(blocks: &'g [Block<'a>]) -> Vec<&'g ImageDescriptor<'a>> {
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => Some(image),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn disassembled_fixtures_assemble_to_the_same_bytes() {
+    for bytes in [
+        &include_bytes!("fixtures/320x240.gif")[..],
+        &include_bytes!("fixtures/disposal.gif")[..],
+        &include_bytes!("fixtures/interleaved.gif")[..],
+        &include_bytes!("fixtures/plain_text.gif")[..],
+        &include_bytes!("fixtures/unknown_extension.gif")[..],
+    ] {
+        let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+        let text = assembly::disassemble(&gif);
+        assert_eq!(assembly::assemble(&text).unwrap(), bytes, "{}", text);
+    }
+}
+
+#[test]
+fn hand_written_text_is_assembled() {
+    let text = r#"
+        ; Two frames, the second one interlaced.
+        version 89a
+        screen logical_screen_width=4 logical_screen_height=4 color_resolution=7
+        colors #000000 #ffffff #ff0000 #00ff00
+        application identifier="NETSCAPE" authentication_code="2.0"
+        sub_block 010000
+        graphic_control disposal_method=2 delay_time=10
+        image image_width=2 image_height=2 lzw_minimum_code_size=2
+        indices 0 1
+        indices 1 0
+        comment
+        sub_block "between \"frames\"\x21"
+        image image_width=4 image_height=4 interlace_flag=1 lzw_minimum_code_size=2
+        colors #000000 #0000ff
+        indices 0 0 0 0
+        indices 1 1 1 1
+        indices 0 1 0 1
+        indices 1 0 1 0
+    "#;
+    let bytes = assembly::assemble(text).unwrap();
+    let gif = decoder::decode(&bytes, &DecodeOptions::new()).unwrap();
+    assert_eq!(gif.logical_screen_descriptor.color_resolution(), 7);
+    assert_eq!(gif.loop_count(), Some(0));
+    let comment = gif.comment_extensions().next().unwrap();
+    assert_eq!(&*comment.data.blocks[0].data, b"between \"frames\"!");
+
+    let images = images(&gif.blocks);
+    let gce = images[0].graphic_control_extension.as_ref().unwrap();
+    assert_eq!(gce.disposal_method(), DisposalMethod::RestoreBackground);
+    assert_eq!(gce.delay_time, 10);
+    assert_eq!(images[0].indices().unwrap(), [0, 1, 1, 0]);
+    assert_eq!(images[1].interlace_flag(), 1);
+    assert_eq!(images[1].local_color_table.as_ref().unwrap().len(), 2);
+    assert_eq!(
+        images[1].indices().unwrap(),
+        [0, 0, 0, 0, 1, 1, 1, 1, 0, 1, 0, 1, 1, 0, 1, 0]
+    );
+
+    // Written back in the same format, with the image data compressed.
+    let text = assembly::disassemble(&gif);
+    assert!(text.contains("\nsub_block \"between \\\"frames\\\"!\"\n"));
+    assert_eq!(assembly::assemble(&text).unwrap(), bytes);
+}
+
+#[test]
+fn invalid_lzw_minimum_code_sizes_are_kept_with_sub_blocks() {
+    let text = "version 89a
+        screen logical_screen_width=1 logical_screen_height=1
+        image image_width=1 image_height=1 lzw_minimum_code_size=200
+        sub_block 0102";
+    let bytes = assembly::assemble(text).unwrap();
+    let options = DecodeOptions::new();
+    let mut stream = decoder::StreamDecoder::new(&bytes[..], &options).unwrap();
+    match stream.next().unwrap().unwrap() {
+        Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+            assert_eq!(image.image_data.lzw_minimum_code_size, 200);
+            assert_eq!(&*image.image_data.image_data.blocks[0].data, [1, 2]);
+        }
+        block => panic!("expected an image, got {:?}", block),
+    }
+}
+
+#[test]
+fn errors_point_to_the_line() {
+    let error = |text: &str| assembly::assemble(text).unwrap_err();
+    let screen = "version 89a\nscreen logical_screen_width=1 logical_screen_height=1\n";
+    assert_eq!(
+        error("screen logical_screen_width=1 logical_screen_height=1"),
+        AssembleError {
+            line: 1,
+            message: "expected 'version' first".to_string()
+        }
+    );
+    assert_eq!(
+        error(&format!(
+            "{}colors #000000 #ffffff #ff0000\ncomment",
+            screen
+        ))
+        .to_string(),
+        "line 2: a color table holds a power of 2 between 2 and 256 colors"
+    );
+    assert_eq!(
+        error(&format!(
+            "{}image image_width=1 lzw_minimum_code_size=2",
+            screen
+        ))
+        .to_string(),
+        "line 3: missing image_height"
+    );
+    assert_eq!(
+        error(&format!("{}comment delay_time=1", screen)).to_string(),
+        "line 3: unknown argument 'delay_time'"
+    );
+    assert_eq!(
+        error(&format!("{}application identifier=\"NETSCAPE\"", screen)).to_string(),
+        "line 3: missing authentication_code"
+    );
+    assert_eq!(
+        error(&format!("{}comment\n\nsub_block \"unterminated", screen)).to_string(),
+        "line 5: unterminated string"
+    );
+    assert_eq!(
+        error(&format!("{}graphic_control\nsub_block 00", screen)).to_string(),
+        "line 4: 'sub_block' can't follow 'graphic_control'"
+    );
+    assert_eq!(
+        error(&format!(
+            "{}image image_width=1 image_height=1 lzw_minimum_code_size=12\nindices 0",
+            screen
+        ))
+        .to_string(),
+        "line 3: lzw_minimum_code_size=12 can't compress 'indices', it must be between 2 and 8"
+    );
+}
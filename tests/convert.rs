@@ -0,0 +1,164 @@
+use giffer::{
+    convert::{Loss, Target},
+    decoder::{self, DecodeOptions},
+    validate, Block, DisposalMethod, GifData, GraphicControlExtension, GraphicRenderingBlock,
+    Version,
+};
+use std::time::Duration;
+
+fn first_frame(gif: &GifData) -> Vec<u8> {
+    gif.frames().next().unwrap().unwrap().pixels
+}
+
+#[test]
+fn animations_are_flattened_to_their_first_frame() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    assert_eq!(gif.minimal_version(), Version::V89a);
+
+    let conversion = gif.convert(Target::V87a).unwrap();
+    assert_eq!(
+        conversion.losses,
+        [
+            Loss::LoopCount(0),
+            Loss::Comments { count: 2 },
+            Loss::Animation { frames: 1 },
+            Loss::GraphicControl { count: 2 },
+        ]
+    );
+    let encoded = conversion.gif.encode(&Version::V87a, false).unwrap();
+    assert_eq!(&encoded[..6], b"GIF87a");
+    assert_eq!(validate::validate_bytes(&encoded).unwrap(), []);
+
+    let converted = decoder::decode(&encoded, &DecodeOptions::new()).unwrap();
+    assert_eq!(converted.minimal_version(), Version::V87a);
+    assert_eq!(converted.blocks.len(), 1);
+    assert_eq!(first_frame(&converted), first_frame(&gif));
+
+    // Nothing else is lost on the way back.
+    let conversion = converted.convert(Target::V89a).unwrap();
+    assert!(conversion.losses.is_empty());
+    assert_eq!(conversion.gif.version, Version::V89a);
+}
+
+#[test]
+fn transparency_is_replaced_by_the_background_color() {
+    let bytes = include_bytes!("fixtures/disposal.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let conversion = gif.convert(Target::V87a).unwrap();
+    assert!(conversion.losses.contains(&Loss::Transparency));
+
+    let lsd = &gif.logical_screen_descriptor;
+    let background = match &lsd.global_color_table {
+        Some(table) => {
+            let pixel = table.get_pixel(lsd.background_color_index as usize);
+            [pixel[0], pixel[1], pixel[2], 0xff]
+        }
+        None => [0, 0, 0, 0xff],
+    };
+    let converted = first_frame(&conversion.gif);
+    for (pixel, original) in converted.chunks(4).zip(first_frame(&gif).chunks(4)) {
+        if original[3] == 0xff {
+            assert_eq!(pixel, original);
+        } else {
+            assert_eq!(pixel, background);
+        }
+    }
+}
+
+#[test]
+fn auto_picks_the_oldest_version_able_to_hold_every_block() {
+    let bytes = include_bytes!("fixtures/plain_text.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let conversion = gif.convert(Target::Auto).unwrap();
+    assert_eq!(conversion.gif.version, Version::V89a);
+    assert!(conversion.losses.is_empty());
+
+    let mut gif = gif.convert(Target::V87a).unwrap().gif;
+    gif.version = Version::V89a;
+    let conversion = gif.convert(Target::Auto).unwrap();
+    assert_eq!(conversion.gif.version, Version::V87a);
+    assert!(conversion.losses.is_empty());
+}
+
+#[test]
+fn graphic_control_extensions_are_only_reported_if_they_did_something() {
+    let bytes = include_bytes!("fixtures/320x240.gif");
+    let mut gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    gif.blocks.truncate(1);
+    let set_graphic_control_extension = |gif: &mut GifData, ext| match &mut gif.blocks[0] {
+        Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+            image.graphic_control_extension = ext
+        }
+        block => panic!("unexpected {:?}", block),
+    };
+
+    let ext = GraphicControlExtension::new(DisposalMethod::Unspecified, Duration::ZERO, None);
+    set_graphic_control_extension(&mut gif, Some(ext));
+    let conversion = gif.convert(Target::V87a).unwrap();
+    assert!(conversion.losses.is_empty());
+    assert_eq!(conversion.gif.minimal_version(), Version::V87a);
+
+    let ext = GraphicControlExtension::new(
+        DisposalMethod::Unspecified,
+        Duration::from_millis(100),
+        None,
+    );
+    set_graphic_control_extension(&mut gif, Some(ext));
+    let conversion = gif.convert(Target::V87a).unwrap();
+    assert_eq!(conversion.losses, [Loss::GraphicControl { count: 1 }]);
+    assert_eq!(conversion.gif.minimal_version(), Version::V87a);
+}
+
+#[test]
+fn converting_to_gif87a_clears_its_reserved_bits() {
+    let mut bytes = include_bytes!("fixtures/320x240.gif").to_vec();
+    // The sort flag of the logical screen descriptor, then the sort flag and the reserved bits
+    // of both image descriptors.
+    bytes[10] |= 0b0000_1000;
+    bytes[33 + 9] |= 0b0011_1000;
+    bytes[2675 + 9] |= 0b0011_1000;
+    let gif = decoder::decode(&bytes, &DecodeOptions::new()).unwrap();
+    let packed_fields = |encoded: &[u8]| {
+        let gif = decoder::decode(encoded, &DecodeOptions::new()).unwrap();
+        let images: Vec<_> = gif
+            .graphic_rendering_blocks()
+            .map(|block| match block {
+                GraphicRenderingBlock::Image(image) => encoded[image.span.unwrap().start + 9],
+                GraphicRenderingBlock::PlainText(_) => panic!("unexpected plain text"),
+            })
+            .collect();
+        (encoded[10], images)
+    };
+    assert_eq!(
+        packed_fields(&gif.encode(&Version::V89a, false).unwrap()),
+        (0x99, vec![0x38, 0x38])
+    );
+
+    // Dropping a graphic control extension, then with nothing to drop.
+    let mut first_image = gif.clone();
+    first_image.blocks.truncate(1);
+    if let Block::GraphicRendering(GraphicRenderingBlock::Image(image)) = &mut first_image.blocks[0]
+    {
+        image.graphic_control_extension = Some(GraphicControlExtension::new(
+            DisposalMethod::Unspecified,
+            Duration::ZERO,
+            None,
+        ));
+    }
+    let mut without_graphic_control_extensions = gif;
+    for block in &mut without_graphic_control_extensions.blocks {
+        if let Block::GraphicRendering(GraphicRenderingBlock::Image(image)) = block {
+            image.graphic_control_extension = None;
+        }
+    }
+    for (gif, images) in [
+        (first_image, vec![0]),
+        (without_graphic_control_extensions, vec![0, 0]),
+    ] {
+        let conversion = gif.convert(Target::V87a).unwrap();
+        assert!(conversion.losses.is_empty());
+        let encoded = conversion.gif.encode(&Version::V87a, false).unwrap();
+        assert_eq!(packed_fields(&encoded), (0x91, images));
+    }
+}
@@ -0,0 +1,577 @@
+use giffer::{
+    decoder::{self, DecodeOptions, Event, Limit, UnknownExtensions},
+    validate::{self, Problem},
+    Block, CommentExtension, DataSubBlocks, GifData, GifError, GraphicRenderingBlock,
+    ImageDescriptor, Span, Version,
+};
+
+const FIXTURE_320X240: &[u8] = include_bytes!("fixtures/320x240.gif");
+
+fn images<'a, 'b>(gif: &'b GifData<'a>) -> Vec<&'b ImageDescriptor<'a>> {
+    gif.graphic_rendering_blocks()
+        .filter_map(|block| match block {
+            GraphicRenderingBlock::Image(image) => Some(image),
+            GraphicRenderingBlock::PlainText(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn logical_screen_dimensions_are_little_endian() {
+    let gif = decoder::decode(FIXTURE_320X240, &DecodeOptions::new()).unwrap();
+    assert_eq!(gif.version, Version::V89a);
+    assert_eq!(gif.logical_screen_descriptor.logical_screen_width, 320);
+    assert_eq!(gif.logical_screen_descriptor.logical_screen_height, 240);
+}
+
+#[test]
+fn image_descriptor_fields_are_little_endian() {
+    let gif = decoder::decode(FIXTURE_320X240, &DecodeOptions::new()).unwrap();
+    let images = images(&gif);
+    assert_eq!(images.len(), 2);
+
+    assert_eq!(images[0].image_left_position, 0);
+    assert_eq!(images[0].image_top_position, 0);
+    assert_eq!(images[0].image_width, 320);
+    assert_eq!(images[0].image_height, 240);
+
+    assert_eq!(images[1].image_left_position, 16);
+    assert_eq!(images[1].image_top_position, 32);
+    assert_eq!(images[1].image_width, 100);
+    assert_eq!(images[1].image_height, 50);
+}
+
+#[test]
+fn delay_times_are_little_endian() {
+    let gif = decoder::decode(FIXTURE_320X240, &DecodeOptions::new()).unwrap();
+    let delays: Vec<_> = images(&gif)
+        .iter()
+        .map(|image| image.graphic_control_extension.as_ref().unwrap().delay_time)
+        .collect();
+    assert_eq!(delays, [10, 250]);
+}
+
+#[test]
+fn round_trip_is_byte_exact() {
+    let gif = decoder::decode(FIXTURE_320X240, &DecodeOptions::new()).unwrap();
+    assert_eq!(gif.encode(&Version::V89a, false).unwrap(), FIXTURE_320X240);
+}
+
+#[test]
+fn round_trip_preserves_block_order() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    assert_eq!(gif.application_extensions().count(), 1);
+    assert_eq!(gif.comment_extensions().count(), 2);
+    assert_eq!(gif.graphic_rendering_blocks().count(), 2);
+    assert_eq!(gif.encode(&Version::V89a, false).unwrap(), &bytes[..]);
+}
+
+#[test]
+fn image_data_decompresses_to_color_indices() {
+    let gif = decoder::decode(FIXTURE_320X240, &DecodeOptions::new()).unwrap();
+    let images = images(&gif);
+    let expected: Vec<u8> = (0..240)
+        .flat_map(|y| (0..320).map(move |x| ((x / 40 + y / 40) % 4) as u8))
+        .collect();
+    assert_eq!(images[0].indices().unwrap(), expected);
+    let expected: Vec<u8> = (0..50)
+        .flat_map(|y| (0..100).map(move |x| ((x * y) % 4) as u8))
+        .collect();
+    assert_eq!(images[1].indices().unwrap(), expected);
+}
+
+#[test]
+fn huge_declared_images_with_little_data_are_too_short() {
+    let mut bytes = FIXTURE_320X240.to_vec();
+    // The width and height of the first image.
+    bytes[33 + 5..33 + 9].copy_from_slice(&[0xff; 4]);
+    let gif = decoder::decode(&bytes, &DecodeOptions::new()).unwrap();
+    assert!(matches!(
+        images(&gif)[0].indices(),
+        Err(GifError::ImageDataTooShort { decoded: 76800, .. })
+    ));
+}
+
+#[test]
+fn stream_decoder_matches_slice_decoder() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let mut stream = decoder::StreamDecoder::new(&bytes[..], &DecodeOptions::new()).unwrap();
+    assert_eq!(*stream.version(), gif.version);
+    assert_eq!(
+        format!("{:?}", stream.logical_screen_descriptor()),
+        format!("{:?}", gif.logical_screen_descriptor)
+    );
+    let blocks: Vec<_> = stream.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(format!("{:?}", blocks), format!("{:?}", gif.blocks));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn stream_decoder_reports_truncation() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let truncated = &bytes[..bytes.len() - 10];
+    let result: Result<Vec<_>, _> = decoder::StreamDecoder::new(truncated, &DecodeOptions::new())
+        .unwrap()
+        .collect();
+    match result.unwrap_err() {
+        GifError::UnexpectedEof { offset, .. } => assert_eq!(offset, truncated.len()),
+        e => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn unknown_extensions_are_kept() {
+    let bytes = include_bytes!("fixtures/unknown_extension.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let labels: Vec<_> = gif
+        .blocks
+        .iter()
+        .filter_map(|block| match block {
+            Block::Unknown { label, .. } => Some(*label),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(labels, [0x99, 0x02]);
+    assert_eq!(gif.encode(&Version::V89a, false).unwrap(), &bytes[..]);
+}
+
+#[test]
+fn plain_text_and_orphaned_graphic_control_extensions_round_trip() {
+    let bytes = include_bytes!("fixtures/plain_text.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let delays: Vec<_> = gif
+        .blocks
+        .iter()
+        .map(|block| match block {
+            Block::GraphicRendering(GraphicRenderingBlock::PlainText(ext)) => {
+                ("plain text", ext.graphic_control_extension.as_ref())
+            }
+            Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+                ("image", image.graphic_control_extension.as_ref())
+            }
+            Block::GraphicControl(ext) => ("graphic control", Some(ext)),
+            Block::Comment(_) => ("comment", None),
+            _ => ("other", None),
+        })
+        .map(|(kind, ext)| (kind, ext.map(|ext| ext.delay_time)))
+        .collect();
+    assert_eq!(
+        delays,
+        [
+            ("plain text", Some(10)),
+            ("graphic control", Some(20)),
+            ("image", Some(30)),
+            ("graphic control", Some(40)),
+            ("comment", None),
+            ("image", None),
+            ("graphic control", Some(50)),
+        ]
+    );
+    assert_eq!(gif.encode(&Version::V89a, false).unwrap(), &bytes[..]);
+
+    let stream: Vec<_> = decoder::StreamDecoder::new(&bytes[..], &DecodeOptions::new())
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(format!("{:?}", stream), format!("{:?}", gif.blocks));
+}
+
+#[test]
+fn plain_text_without_global_color_table_is_kept() {
+    let bytes = include_bytes!("fixtures/plain_text.gif");
+    let mut gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    gif.logical_screen_descriptor.set_global_color_table(None);
+    let bytes = gif.encode(&Version::V89a, false).unwrap();
+
+    let gif = decoder::decode(&bytes, &DecodeOptions::new()).unwrap();
+    match &gif.blocks[0] {
+        Block::GraphicRendering(GraphicRenderingBlock::PlainText(ext)) => {
+            assert_eq!(
+                ext.graphic_control_extension.as_ref().unwrap().delay_time,
+                10
+            );
+        }
+        block => panic!("unexpected {:?}", block),
+    }
+    assert_eq!(gif.encode(&Version::V89a, false).unwrap(), bytes);
+    let problems: Vec<_> = validate::validate(&gif)
+        .into_iter()
+        .map(|diagnostic| diagnostic.problem)
+        .collect();
+    assert!(problems.contains(&Problem::PlainTextWithoutGlobalColorTable));
+}
+
+#[test]
+fn spans_point_back_to_the_decoded_bytes() {
+    fn check_sub_blocks(bytes: &[u8], data: &DataSubBlocks, end: usize) {
+        let mut offset = None;
+        for block in &data.blocks {
+            let span = block.span.unwrap();
+            assert!(offset.map_or(true, |offset| offset == span.start));
+            assert_eq!(bytes[span.start], block.block_size);
+            assert_eq!(&bytes[span.start + 1..span.end], &*block.data);
+            offset = Some(span.end);
+        }
+        // Followed by the block terminator, which ends the enclosing block.
+        assert_eq!(bytes[offset.unwrap_or(end - 1)], 0);
+    }
+
+    for bytes in [
+        &include_bytes!("fixtures/interleaved.gif")[..],
+        &include_bytes!("fixtures/plain_text.gif")[..],
+        FIXTURE_320X240,
+    ] {
+        let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+        let lsd = &gif.logical_screen_descriptor;
+        assert_eq!(lsd.span.unwrap().range(), 6..13);
+        if let Some(table) = &lsd.global_color_table {
+            assert_eq!(&bytes[table.span.unwrap().range()], table.pixels());
+        }
+
+        let mut end = 13
+            + lsd
+                .global_color_table
+                .as_ref()
+                .map_or(0, |t| t.span.unwrap().len());
+        for block in &gif.blocks {
+            let (gce, span, data) = match block {
+                Block::Application(ext) => (None, ext.span.unwrap(), &ext.data),
+                Block::Comment(ext) => (None, ext.span.unwrap(), &ext.data),
+                Block::Unknown { data, span, .. } => (None, span.unwrap(), data),
+                Block::GraphicControl(ext) => {
+                    let span = ext.span.unwrap();
+                    assert_eq!(&bytes[span.start..span.start + 3], b"\x21\xf9\x04");
+                    assert_eq!(span.len(), 8);
+                    end = span.end;
+                    continue;
+                }
+                Block::GraphicRendering(GraphicRenderingBlock::PlainText(ext)) => (
+                    ext.graphic_control_extension.as_ref(),
+                    ext.span.unwrap(),
+                    &ext.data,
+                ),
+                Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+                    let span = image.span.unwrap();
+                    assert_eq!((bytes[span.start], span.len()), (0x2c, 10));
+                    let mut data_start = span.end;
+                    if let Some(table) = &image.local_color_table {
+                        assert_eq!(table.span.unwrap().start, span.end);
+                        data_start = table.span.unwrap().end;
+                    }
+                    let image_data = image.image_data.span.unwrap();
+                    assert_eq!(image_data.start, data_start);
+                    assert_eq!(bytes[data_start], image.image_data.lzw_minimum_code_size);
+                    check_sub_blocks(bytes, &image.image_data.image_data, image_data.end);
+                    (
+                        image.graphic_control_extension.as_ref(),
+                        Span {
+                            start: span.start,
+                            end: image_data.end,
+                        },
+                        &image.image_data.image_data,
+                    )
+                }
+            };
+            if let Some(gce) = gce {
+                assert_eq!(gce.span.unwrap().start, end);
+                end = gce.span.unwrap().end;
+            }
+            assert_eq!(span.start, end);
+            if bytes[span.start] == 0x21 {
+                check_sub_blocks(bytes, data, span.end);
+            }
+            end = span.end;
+        }
+        // Blocks follow one another up to the trailer.
+        assert_eq!(&bytes[end..], b"\x3b");
+    }
+}
+
+fn push_decode(
+    bytes: &[u8],
+    chunk_size: usize,
+    options: &DecodeOptions,
+) -> (Version, Vec<Block<'static>>, Vec<GifError>) {
+    let mut decoder = decoder::PushDecoder::new(options);
+    let (mut version, mut blocks, mut image, mut warnings) = (None, Vec::new(), None, Vec::new());
+    let mut on_event = |event: Event<'_>| match event {
+        Event::Header(v) => version = Some(v),
+        Event::LogicalScreenDescriptor(_) | Event::End => {}
+        Event::Extension(block) => blocks.push(block),
+        Event::FrameStart(i) => image = Some(i),
+        Event::FrameData(block) => {
+            let image: &mut ImageDescriptor = image.as_mut().unwrap();
+            image.image_data.image_data.blocks.push(block.into_owned());
+        }
+        Event::FrameEnd(span) => {
+            let mut image = image.take().unwrap();
+            image.image_data.span = Some(span);
+            blocks.push(Block::GraphicRendering(GraphicRenderingBlock::Image(image)));
+        }
+        Event::Warning(warning) => warnings.push(warning),
+    };
+    for chunk in bytes.chunks(chunk_size) {
+        decoder.feed(chunk, &mut on_event).unwrap();
+    }
+    decoder.finish(&mut on_event).unwrap();
+    (version.unwrap(), blocks, warnings)
+}
+
+#[test]
+fn push_decoder_matches_slice_decoder() {
+    for bytes in [
+        &include_bytes!("fixtures/interleaved.gif")[..],
+        &include_bytes!("fixtures/plain_text.gif")[..],
+        FIXTURE_320X240,
+    ] {
+        let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+        for chunk_size in [1, 7, 300, bytes.len()] {
+            let (version, blocks, _) = push_decode(bytes, chunk_size, &DecodeOptions::new());
+            assert_eq!(version, gif.version);
+            assert_eq!(format!("{:?}", blocks), format!("{:?}", gif.blocks));
+        }
+    }
+}
+
+#[test]
+fn push_decoder_resumes_long_extensions_after_the_last_sub_block() {
+    let mut gif = decoder::decode(FIXTURE_320X240, &DecodeOptions::new()).unwrap();
+    gif.blocks.insert(
+        0,
+        Block::Comment(CommentExtension {
+            data: DataSubBlocks::from_data(&[b'x'; 1 << 20]),
+            span: None,
+        }),
+    );
+    let bytes = gif.encode(&gif.version, false).unwrap();
+    let gif = decoder::decode(&bytes, &DecodeOptions::new()).unwrap();
+    // Fed a byte at a time, this only finishes quickly if the sub-blocks already received aren't
+    // decoded again with every byte.
+    let (_, blocks, _) = push_decode(&bytes, 1, &DecodeOptions::new());
+    assert_eq!(format!("{:?}", blocks), format!("{:?}", gif.blocks));
+}
+
+#[test]
+fn push_decoder_reports_truncation_and_errors() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let mut decoder = decoder::PushDecoder::new(&DecodeOptions::new());
+    decoder.feed(&bytes[..bytes.len() - 10], |_| {}).unwrap();
+    assert!(!decoder.is_finished());
+    assert!(matches!(
+        decoder.finish(|_| {}),
+        Err(GifError::UnexpectedEof { .. })
+    ));
+
+    let mut decoder = decoder::PushDecoder::new(&DecodeOptions::new());
+    let error = decoder.feed(b"GIT89a", |_| {}).unwrap_err();
+    assert!(matches!(
+        error,
+        GifError::InvalidSignature { offset: 0, .. }
+    ));
+    assert_eq!(decoder.feed(bytes, |_| {}), Err(error));
+}
+
+#[test]
+fn decode_options_select_extensions() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let options = DecodeOptions::new()
+        .discard_comments(true)
+        .discard_application_extensions(true);
+    let gif = decoder::decode(bytes, &options).unwrap();
+    assert_eq!(gif.application_extensions().count(), 0);
+    assert_eq!(gif.comment_extensions().count(), 0);
+    assert_eq!(gif.graphic_rendering_blocks().count(), 2);
+
+    let bytes = include_bytes!("fixtures/unknown_extension.gif");
+    let options = DecodeOptions::new().unknown_extensions(UnknownExtensions::Discard);
+    let gif = decoder::decode(bytes, &options).unwrap();
+    assert!(!gif
+        .blocks
+        .iter()
+        .any(|block| matches!(block, Block::Unknown { .. })));
+    let options = DecodeOptions::new().unknown_extensions(UnknownExtensions::Reject);
+    assert!(matches!(
+        decoder::decode(bytes, &options),
+        Err(GifError::UnknownLabel { label: 0x99, .. })
+    ));
+
+    let bytes = include_bytes!("fixtures/plain_text.gif");
+    let options = DecodeOptions::new()
+        .discard_graphic_control_extensions(true)
+        .discard_plain_text_extensions(true);
+    let gif = decoder::decode(bytes, &options).unwrap();
+    assert!(gif.blocks.iter().all(|block| match block {
+        Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => {
+            image.graphic_control_extension.is_none()
+        }
+        Block::Comment(_) => true,
+        _ => false,
+    }));
+    assert_eq!(images(&gif).len(), 2);
+    // Only the graphic control extension of the plain text is dropped with it.
+    let options = DecodeOptions::new().discard_plain_text_extensions(true);
+    let gif = decoder::decode(bytes, &options).unwrap();
+    assert_eq!(gif.graphic_rendering_blocks().count(), 2);
+    assert_eq!(
+        gif.blocks
+            .iter()
+            .filter(|block| matches!(block, Block::GraphicControl(_)))
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn decode_options_enforce_limits() {
+    let limit_exceeded = |options: DecodeOptions| {
+        let error = decoder::decode(FIXTURE_320X240, &options).unwrap_err();
+        let stream_error = decoder::StreamDecoder::new(FIXTURE_320X240, &options)
+            .and_then(|stream| stream.collect::<Result<Vec<_>, _>>())
+            .unwrap_err();
+        assert_eq!(stream_error, error);
+        match error {
+            GifError::LimitExceeded {
+                offset,
+                limit,
+                value,
+                max,
+            } => (offset, limit, value, max),
+            e => panic!("unexpected error: {}", e),
+        }
+    };
+    assert_eq!(
+        limit_exceeded(DecodeOptions::new().max_logical_screen_size(320, 200)),
+        (6, Limit::LogicalScreenHeight, 240, 200)
+    );
+    assert_eq!(
+        limit_exceeded(DecodeOptions::new().max_canvas_pixels(320 * 239)),
+        (6, Limit::CanvasPixels, 320 * 240, 320 * 239)
+    );
+    let (_, limit, value, max) = limit_exceeded(DecodeOptions::new().max_frames(1));
+    assert_eq!((limit, value, max), (Limit::Frames, 2, 1));
+    let (_, limit, value, max) = limit_exceeded(DecodeOptions::new().max_frame_size(200, 200));
+    assert_eq!((limit, value, max), (Limit::FrameWidth, 320, 200));
+    let (_, limit, value, max) = limit_exceeded(DecodeOptions::new().max_frame_pixels(320 * 239));
+    assert_eq!(
+        (limit, value, max),
+        (Limit::FramePixels, 320 * 240, 320 * 239)
+    );
+    let (offset, limit, value, max) =
+        limit_exceeded(DecodeOptions::new().max_total_pixels(320 * 240 + 100));
+    assert_eq!(FIXTURE_320X240[offset], 0x2c);
+    assert_eq!(
+        (limit, value, max),
+        (Limit::TotalPixels, 320 * 240 + 100 * 50, 320 * 240 + 100)
+    );
+
+    let options = DecodeOptions::new()
+        .max_logical_screen_size(320, 240)
+        .max_frame_size(320, 240)
+        .max_frames(2)
+        .max_total_pixels(320 * 240 + 100 * 50)
+        .max_canvas_pixels(320 * 240);
+    assert!(decoder::decode(FIXTURE_320X240, &options).is_ok());
+}
+
+/// Encodes `blocks` in place of the blocks of `gif`.
+fn encode_blocks<'a>(gif: &GifData<'a>, blocks: &[Block<'a>]) -> Vec<u8> {
+    let gif = GifData {
+        version: gif.version,
+        logical_screen_descriptor: gif.logical_screen_descriptor.clone(),
+        blocks: blocks.to_vec(),
+        trailer: None,
+    };
+    gif.encode(&gif.version, false).unwrap()
+}
+
+#[test]
+fn lenient_decoding_recovers_from_damaged_files() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let clean = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let lenient = DecodeOptions::new().lenient(true);
+    let check = |damaged: &[u8], expected_blocks: &[Block], expected_warnings: &[GifError]| {
+        assert!(decoder::decode(damaged, &DecodeOptions::new()).is_err());
+        // The blocks are compared by their encoding, as their spans differ.
+        let expected = encode_blocks(&clean, expected_blocks);
+        let (gif, warnings) = decoder::decode_with_warnings(damaged, &lenient).unwrap();
+        assert_eq!(encode_blocks(&clean, &gif.blocks), expected);
+        assert_eq!(warnings, expected_warnings);
+
+        let mut stream = decoder::StreamDecoder::new(damaged, &lenient).unwrap();
+        let blocks: Vec<_> = stream.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(encode_blocks(&clean, &blocks), expected);
+        assert_eq!(stream.warnings(), expected_warnings);
+
+        let (_, blocks, warnings) = push_decode(damaged, 5, &lenient);
+        assert_eq!(encode_blocks(&clean, &blocks), expected);
+        assert_eq!(warnings.len(), expected_warnings.len());
+    };
+
+    // Missing trailer.
+    let damaged = &bytes[..bytes.len() - 1];
+    let eof = GifError::UnexpectedEof {
+        offset: damaged.len(),
+        needed: 1,
+    };
+    check(damaged, &clean.blocks, &[eof]);
+
+    // Garbage between blocks.
+    let mut damaged = bytes.to_vec();
+    damaged.splice(bytes.len() - 1..bytes.len() - 1, *b"\x00junk");
+    let garbage = GifError::UnknownBlock {
+        offset: bytes.len() - 1,
+        byte: 0,
+    };
+    check(&damaged, &clean.blocks, &[garbage]);
+
+    // Graphic control extension with the wrong block size.
+    let gce = bytes.windows(3).position(|w| w == b"\x21\xf9\x04").unwrap();
+    let mut damaged = bytes.to_vec();
+    damaged[gce + 2] = 5;
+    damaged.insert(gce + 7, 0xaa);
+    let bad_size = GifError::BadBlockSize {
+        offset: gce + 2,
+        expected: 4,
+        found: 5,
+    };
+    check(&damaged, &clean.blocks, &[bad_size]);
+
+    // Truncated last comment, in the middle of its only sub-block, which keeps the bytes received.
+    let comment = clean
+        .blocks
+        .iter()
+        .rposition(|block| matches!(block, Block::Comment(_)))
+        .unwrap();
+    let mut blocks = clean.blocks[..=comment].to_vec();
+    if let Block::Comment(ext) = &mut blocks[comment] {
+        let span = ext.span.unwrap();
+        // Without the last 4 bytes of data and the block terminator.
+        let damaged = &bytes[..span.end - 5];
+        ext.data = DataSubBlocks::from_data(&damaged[span.start + 3..]);
+        let eof = GifError::UnexpectedEof {
+            offset: damaged.len(),
+            needed: 4,
+        };
+        check(damaged, &blocks, &[eof]);
+    }
+
+    // Truncated last image, in the middle of its only sub-block, which keeps the bytes received.
+    let separator = bytes.iter().rposition(|&b| b == 0x2c).unwrap();
+    let damaged = &bytes[..separator + 14];
+    let last_image = clean
+        .blocks
+        .iter()
+        .rposition(|block| matches!(block, Block::GraphicRendering(_)))
+        .unwrap();
+    let mut blocks = clean.blocks[..=last_image].to_vec();
+    if let Block::GraphicRendering(GraphicRenderingBlock::Image(image)) = &mut blocks[last_image] {
+        image.image_data.image_data = DataSubBlocks::from_data(&damaged[separator + 12..]);
+    }
+    let eof = GifError::UnexpectedEof {
+        offset: separator + 14,
+        needed: 4,
+    };
+    check(damaged, &blocks, &[eof]);
+}
@@ -0,0 +1,137 @@
+use giffer::{
+    decoder::{self, DecodeOptions},
+    dump, Block, CommentExtension, DataSubBlocks, GifData, GraphicRenderingBlock, ImageDescriptor,
+    LogicalScreenDescriptor, TableBasedImageData, Version,
+};
+
+#[test]
+fn every_block_is_dumped_with_its_offsets() {
+    let bytes = include_bytes!("fixtures/plain_text.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let text = dump::text(&gif, false);
+    for line in [
+        "header at 0..6",
+        "logical screen descriptor at 6..13",
+        "    global color table at 13..25",
+        "        colors: 4",
+        "plain text extension at 33..52",
+        "    graphic control extension at 25..33",
+        "        disposal: none",
+        "comment extension at 95..106",
+        "    text: \"between\"",
+    ] {
+        assert!(
+            text.lines().any(|l| l == line),
+            "{:?} missing from\n{}",
+            line,
+            text
+        );
+    }
+    let trailer = format!("trailer at {}..{}", bytes.len() - 1, bytes.len());
+    assert_eq!(text.lines().last(), Some(trailer.as_str()));
+    assert!(!text.contains('\x1b'));
+
+    let json = dump::json(&gif);
+    assert!(json.starts_with(
+        "{\"blocks\":[{\"kind\":\"header\",\"start\":0,\"end\":6,\"fields\":{\"version\":\"89a\"}"
+    ));
+    let images = gif
+        .graphic_rendering_blocks()
+        .filter(|block| matches!(block, GraphicRenderingBlock::Image(_)))
+        .count();
+    assert_eq!(json.matches("{\"kind\":\"image data\"").count(), images);
+    assert!(json.contains("\"colors\":[\"#000000\",\"#ffffff\",\"#ff0000\",\"#0000ff\"]"));
+}
+
+#[test]
+fn image_data_comes_with_lzw_stats() {
+    let bytes = include_bytes!("fixtures/320x240.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let text = dump::text(&gif, false);
+    for line in [
+        "        sub_block_sizes: 255 x 10, 61",
+        "        compressed_bytes: 2611",
+        "        pixels: 76800",
+        "        decoded_pixels: 76800",
+        "        max_code_size: 12",
+        "        end_of_information: true",
+        "        unused_bytes: 0",
+    ] {
+        assert!(
+            text.lines().any(|l| l == line),
+            "{:?} missing from\n{}",
+            line,
+            text
+        );
+    }
+}
+
+#[test]
+fn edited_documents_are_dumped_without_offsets() {
+    let gif = GifData {
+        version: Version::V89a,
+        logical_screen_descriptor: LogicalScreenDescriptor::new(16, 9),
+        blocks: vec![Block::Comment(CommentExtension {
+            data: DataSubBlocks::from_data(b"say \"hi\"\n\x01"),
+            span: None,
+        })],
+        trailer: None,
+    };
+    let json = dump::json(&gif);
+    assert!(json.contains(
+        "{\"kind\":\"comment extension\",\"start\":null,\"end\":null,\
+         \"fields\":{\"text\":\"say \\\"hi\\\"\\n\\u0001\",\"sub_block_sizes\":[10]}"
+    ));
+    assert!(json.ends_with(
+        "{\"kind\":\"trailer\",\"start\":null,\"end\":null,\"fields\":{},\"children\":[]}]}"
+    ));
+
+    let debug = format!("{:?}", gif.logical_screen_descriptor);
+    assert!(debug.contains("logical_screen_width: 16, logical_screen_height: 9"));
+}
+
+#[test]
+fn huge_declared_images_are_dumped_with_the_pixels_decoded() {
+    let gif = GifData {
+        version: Version::V89a,
+        logical_screen_descriptor: LogicalScreenDescriptor::new(1, 1),
+        blocks: vec![Block::GraphicRendering(GraphicRenderingBlock::Image(
+            ImageDescriptor::new(
+                0,
+                0,
+                u16::MAX,
+                u16::MAX,
+                TableBasedImageData::compress(&[0; 4], 2).unwrap(),
+            ),
+        ))],
+        trailer: None,
+    };
+    let text = dump::text(&gif, false);
+    for line in ["        pixels: 4294836225", "        decoded_pixels: 4"] {
+        assert!(
+            text.lines().any(|l| l == line),
+            "{:?} missing from\n{}",
+            line,
+            text
+        );
+    }
+}
+
+#[test]
+fn the_trailer_is_dumped_where_it_was_found() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    // The comment right before the trailer is discarded.
+    let gif = decoder::decode(bytes, &DecodeOptions::new().discard_comments(true)).unwrap();
+    let trailer = format!("trailer at {}..{}", bytes.len() - 1, bytes.len());
+    assert_eq!(
+        dump::text(&gif, false).lines().last(),
+        Some(trailer.as_str())
+    );
+
+    let truncated = &bytes[..bytes.len() - 1];
+    let (gif, _) =
+        decoder::decode_with_warnings(truncated, &DecodeOptions::new().lenient(true)).unwrap();
+    assert!(dump::text(&gif, false)
+        .lines()
+        .all(|line| !line.starts_with("trailer")));
+}
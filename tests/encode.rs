@@ -0,0 +1,329 @@
+use giffer::{
+    decoder::{self, DecodeOptions},
+    encoder::Encoder,
+    ApplicationExtension, Block, ColorTable, DataSubBlock, DisposalMethod, GifData, GifError,
+    GraphicControlExtension, GraphicRenderingBlock, ImageDescriptor, TableBasedImageData, Version,
+};
+use std::{convert::TryFrom, time::Duration};
+
+#[test]
+fn new_frames_round_trip() {
+    let bytes = include_bytes!("fixtures/320x240.gif");
+    let mut gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+
+    let indices: Vec<u8> = (0..64 * 48).map(|i| (i % 7 % 4) as u8).collect();
+    let image_data = TableBasedImageData::compress(&indices, 2).unwrap();
+    assert!(image_data
+        .image_data
+        .blocks
+        .iter()
+        .all(|block| block.block_size as usize == block.data.len() && block.data.len() <= 255));
+    gif.blocks
+        .push(Block::GraphicRendering(GraphicRenderingBlock::Image(
+            ImageDescriptor::new(8, 16, 64, 48, image_data),
+        )));
+
+    let encoded = gif.encode(&Version::V89a, false).unwrap();
+    let gif = decoder::decode(&encoded, &DecodeOptions::new()).unwrap();
+    let image = match gif.graphic_rendering_blocks().last().unwrap() {
+        GraphicRenderingBlock::Image(image) => image,
+        GraphicRenderingBlock::PlainText(_) => unreachable!(),
+    };
+    assert_eq!(
+        (image.image_left_position, image.image_top_position),
+        (8, 16)
+    );
+    assert_eq!(image.indices().unwrap(), indices);
+}
+
+#[test]
+fn streaming_encoder_matches_encode() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+
+    let mut encoder = Encoder::new(
+        Vec::new(),
+        Version::V89a,
+        &gif.logical_screen_descriptor,
+        false,
+    )
+    .unwrap();
+    for block in &gif.blocks {
+        encoder.write_block(block).unwrap();
+    }
+    assert_eq!(encoder.finish().unwrap(), &bytes[..]);
+}
+
+#[test]
+fn edited_owned_documents_round_trip() {
+    let mut gif = {
+        let bytes = include_bytes!("fixtures/320x240.gif").to_vec();
+        decoder::decode(&bytes, &DecodeOptions::new())
+            .unwrap()
+            .into_owned()
+    };
+
+    gif.logical_screen_descriptor.set_sort_flag(true);
+    let local_color_table = ColorTable::new(vec![0x10; 3 * 8]).unwrap();
+    for block in &mut gif.blocks {
+        if let Block::GraphicRendering(GraphicRenderingBlock::Image(image)) = block {
+            image.set_local_color_table(Some(local_color_table.clone()));
+            let ext = image.graphic_control_extension.as_mut().unwrap();
+            ext.set_disposal_method(DisposalMethod::RestoreBackground);
+            ext.set_transparent_color_flag(true);
+        }
+    }
+
+    let encoded = gif.encode(&Version::V89a, false).unwrap();
+    let gif = decoder::decode(&encoded, &DecodeOptions::new()).unwrap();
+    assert_eq!(gif.logical_screen_descriptor.sort_flag(), 1);
+    assert_eq!(gif.logical_screen_descriptor.global_color_table_size(), 1);
+    for block in gif.graphic_rendering_blocks() {
+        if let GraphicRenderingBlock::Image(image) = block {
+            assert_eq!(image.local_color_table_flag(), 1);
+            assert_eq!(image.local_color_table_size(), 2);
+            assert_eq!(image.local_color_table.as_ref().unwrap().len(), 8);
+            let ext = image.graphic_control_extension.as_ref().unwrap();
+            assert_eq!(ext.disposal_method(), DisposalMethod::RestoreBackground);
+            assert_eq!(ext.transparent_color_flag(), 1);
+        }
+    }
+}
+
+#[test]
+fn loop_count_is_edited_or_inserted() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let mut gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let ext = gif.application_extensions().next().unwrap();
+    assert!(ext.is_netscape());
+    assert_eq!(ext.netscape().unwrap().buffering_size, None);
+    assert_eq!(gif.loop_count(), Some(0));
+
+    gif.set_loop_count(3);
+    let encoded = gif.encode(&Version::V89a, false).unwrap();
+    assert_eq!(encoded.len(), bytes.len());
+    let gif = decoder::decode(&encoded, &DecodeOptions::new()).unwrap();
+    assert_eq!(gif.application_extensions().count(), 1);
+    assert_eq!(gif.loop_count(), Some(3));
+
+    let mut gif = decoder::decode(
+        include_bytes!("fixtures/320x240.gif"),
+        &DecodeOptions::new(),
+    )
+    .unwrap();
+    assert_eq!(gif.loop_count(), None);
+    gif.set_loop_count(0);
+    let encoded = gif.encode(&Version::V89a, false).unwrap();
+    let gif = decoder::decode(&encoded, &DecodeOptions::new()).unwrap();
+    assert!(matches!(gif.blocks[0], Block::Application(_)));
+    assert_eq!(gif.loop_count(), Some(0));
+}
+
+#[test]
+fn new_graphic_control_extensions_round_trip() {
+    let mut gif = decoder::decode(
+        include_bytes!("fixtures/320x240.gif"),
+        &DecodeOptions::new(),
+    )
+    .unwrap();
+    let indices = vec![0; 4 * 4];
+    let mut image = ImageDescriptor::new(
+        0,
+        0,
+        4,
+        4,
+        TableBasedImageData::compress(&indices, 2).unwrap(),
+    );
+    image.graphic_control_extension = Some(GraphicControlExtension::new(
+        DisposalMethod::RestorePrevious,
+        Duration::from_millis(1234),
+        Some(3),
+    ));
+    gif.blocks
+        .push(Block::GraphicRendering(GraphicRenderingBlock::Image(image)));
+
+    let encoded = gif.encode(&Version::V89a, false).unwrap();
+    let gif = decoder::decode(&encoded, &DecodeOptions::new()).unwrap();
+    let ext = match gif.graphic_rendering_blocks().last().unwrap() {
+        GraphicRenderingBlock::Image(image) => image.graphic_control_extension.as_ref().unwrap(),
+        GraphicRenderingBlock::PlainText(_) => unreachable!(),
+    };
+    assert_eq!(ext.disposal_method(), DisposalMethod::RestorePrevious);
+    assert_eq!(ext.delay_time, 123);
+    assert_eq!(ext.delay(), Duration::from_millis(1230));
+    assert_eq!(ext.user_input_flag(), 0);
+    assert_eq!(ext.transparent_color_flag(), 1);
+    assert_eq!(ext.transparent_color_index, 3);
+
+    let mut ext = ext.clone();
+    ext.set_disposal_method(DisposalMethod::Reserved(5));
+    assert_eq!(ext.disposal_method(), DisposalMethod::Reserved(5));
+    ext.set_delay(Duration::from_secs(1000));
+    assert_eq!(ext.delay_time, u16::MAX);
+
+    for value in 0..=7 {
+        let disposal_method = DisposalMethod::try_from(value).unwrap();
+        assert_eq!(u8::from(disposal_method), value);
+        ext.set_disposal_method(disposal_method);
+        assert_eq!(ext.disposal_method(), disposal_method);
+    }
+    assert_eq!(DisposalMethod::try_from(8), Err(8));
+}
+
+#[test]
+#[should_panic(expected = "reserved disposal methods go from 4 to 7, got 1")]
+fn reserved_disposal_methods_out_of_range_are_rejected() {
+    let mut ext = GraphicControlExtension::new(DisposalMethod::None, Duration::ZERO, None);
+    ext.set_disposal_method(DisposalMethod::Reserved(1));
+}
+
+#[test]
+fn interlaced_frames_decode_to_natural_order() {
+    assert_eq!(
+        giffer::interlace::rows(10).collect::<Vec<_>>(),
+        [0, 8, 4, 2, 6, 1, 3, 5, 7, 9]
+    );
+
+    let bytes = include_bytes!("fixtures/320x240.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let mut encoder = Encoder::new(
+        Vec::new(),
+        Version::V89a,
+        &gif.logical_screen_descriptor,
+        false,
+    )
+    .unwrap();
+    encoder.set_interlace(true);
+    for block in &gif.blocks {
+        encoder.write_block(block).unwrap();
+    }
+    let encoded = encoder.finish().unwrap();
+
+    let interlaced = decoder::decode(&encoded, &DecodeOptions::new()).unwrap();
+    let pairs = gif
+        .graphic_rendering_blocks()
+        .zip(interlaced.graphic_rendering_blocks());
+    for pair in pairs {
+        if let (GraphicRenderingBlock::Image(image), GraphicRenderingBlock::Image(interlaced)) =
+            pair
+        {
+            assert_eq!(image.interlace_flag(), 0);
+            assert_eq!(interlaced.interlace_flag(), 1);
+            assert_eq!(interlaced.indices().unwrap(), image.indices().unwrap());
+            let raw = interlaced
+                .image_data
+                .decompress(interlaced.indices().unwrap().len())
+                .unwrap();
+            assert_ne!(raw, image.indices().unwrap());
+        }
+    }
+}
+
+fn first_image<'g>(gif: &'g mut GifData<'static>) -> &'g mut ImageDescriptor<'static> {
+    match &mut gif.blocks[0] {
+        Block::GraphicRendering(GraphicRenderingBlock::Image(image)) => image,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn inconsistent_documents_are_rejected() {
+    let bytes = include_bytes!("fixtures/320x240.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let edited = |edit: &dyn Fn(&mut GifData<'static>)| {
+        let mut gif = gif.clone();
+        edit(&mut gif);
+        gif.encode(&Version::V89a, false).unwrap_err()
+    };
+    let colors = gif
+        .logical_screen_descriptor
+        .global_color_table
+        .as_ref()
+        .unwrap()
+        .len();
+    let image = first_image(&mut gif.clone()).clone();
+    let graphic_control_extension_offset =
+        image.graphic_control_extension.unwrap().span.unwrap().start;
+    let image_offset = image.span.unwrap().start;
+    // After the LZW minimum code size.
+    let sub_blocks_offset = image.image_data.span.unwrap().start + 1;
+
+    assert_eq!(
+        edited(&|gif| gif.logical_screen_descriptor.global_color_table = None),
+        GifError::ColorTableMismatch {
+            offset: 6,
+            expected: Some(colors),
+            found: None,
+        }
+    );
+    let bad_color_table = |gif: &mut GifData<'static>| {
+        first_image(gif).local_color_table = ColorTable::new(vec![0; 6]);
+    };
+    assert_eq!(
+        edited(&bad_color_table),
+        GifError::ColorTableMismatch {
+            offset: image_offset,
+            expected: None,
+            found: Some(2),
+        }
+    );
+    let bad_block_size = |gif: &mut GifData<'static>| {
+        first_image(gif).image_data.image_data.blocks[0].data = vec![0; 300].into();
+    };
+    assert_eq!(
+        edited(&bad_block_size),
+        GifError::SubBlockSizeMismatch {
+            offset: sub_blocks_offset,
+            block_size: 255,
+            len: 300,
+        }
+    );
+    // It would be written as the block terminator.
+    let empty_sub_block = |gif: &mut GifData<'static>| {
+        first_image(gif).image_data.image_data.blocks[0] = DataSubBlock {
+            block_size: 0,
+            data: Vec::new().into(),
+            span: None,
+        };
+    };
+    assert_eq!(
+        edited(&empty_sub_block),
+        GifError::EmptySubBlock {
+            offset: sub_blocks_offset,
+        }
+    );
+
+    // Nothing of a rejected image is written, not even its graphic control extension.
+    for edit in [
+        &bad_color_table as &dyn Fn(&mut GifData<'static>),
+        &bad_block_size,
+        &empty_sub_block,
+    ] {
+        let mut gif = gif.clone();
+        edit(&mut gif);
+        let mut encoder = Encoder::new(
+            Vec::new(),
+            Version::V89a,
+            &gif.logical_screen_descriptor,
+            false,
+        )
+        .unwrap();
+        assert!(encoder.write_block(&gif.blocks[0]).is_err());
+        assert_eq!(
+            encoder.finish().unwrap().len(),
+            graphic_control_extension_offset + 1
+        );
+    }
+    assert_eq!(
+        edited(&|gif| {
+            let mut ext = ApplicationExtension::looping(0);
+            ext.identifier = b"NETSCAPE2.0".to_vec().into();
+            gif.blocks.insert(0, Block::Application(ext));
+        }),
+        GifError::BadBlockSize {
+            offset: 13 + colors * 3,
+            expected: 11,
+            found: 14,
+        }
+    );
+}
@@ -0,0 +1,101 @@
+use giffer::{interlace, progressive::RowDecoder, GifError, ImageDescriptor, TableBasedImageData};
+
+fn image(indices: &[u8], width: u16, height: u16, interlaced: bool) -> ImageDescriptor<'static> {
+    let indices = if interlaced {
+        interlace::interlace(indices, width as usize)
+    } else {
+        indices.to_vec()
+    };
+    let mut image = ImageDescriptor::new(
+        0,
+        0,
+        width,
+        height,
+        TableBasedImageData::compress(&indices, 4).unwrap(),
+    );
+    image.set_interlace_flag(interlaced);
+    image
+}
+
+#[test]
+fn interlaced_rows_are_reported_pass_by_pass() {
+    let indices: Vec<u8> = (0..10 * 11).map(|i| (i / 10) as u8).collect();
+    let image = image(&indices, 10, 11, true);
+
+    let mut rows = Vec::new();
+    let mut preview = vec![0xff; indices.len()];
+    image
+        .decode_rows(|row| {
+            rows.push((row.y, row.pass, row.height));
+            for y in row.y..row.y + row.height {
+                preview[y * 10..(y + 1) * 10].copy_from_slice(row.indices);
+            }
+            if row.pass == 1 {
+                assert!(row.indices.iter().all(|&i| i as usize == row.y));
+            }
+        })
+        .unwrap();
+    assert_eq!(
+        rows,
+        [
+            (0, 1, 8),
+            (8, 1, 3),
+            (4, 2, 4),
+            (2, 3, 2),
+            (6, 3, 2),
+            (10, 3, 1),
+            (1, 4, 1),
+            (3, 4, 1),
+            (5, 4, 1),
+            (7, 4, 1),
+            (9, 4, 1),
+        ]
+    );
+    assert_eq!(preview, indices);
+}
+
+#[test]
+fn partially_received_image_data_reports_complete_rows() {
+    let indices: Vec<u8> = (0..64 * 64).map(|i| (i * 7 % 13) as u8).collect();
+    let image = image(&indices, 64, 64, false);
+    let data: Vec<u8> = image
+        .image_data
+        .image_data
+        .blocks
+        .iter()
+        .flat_map(|block| block.data.iter().copied())
+        .collect();
+
+    let mut decoder = RowDecoder::new(&image).unwrap();
+    let mut received = Vec::new();
+    decoder
+        .feed(&data[..data.len() / 2], |row| {
+            assert_eq!(row.y, received.len() / 64);
+            received.extend_from_slice(row.indices);
+        })
+        .unwrap();
+    assert!(!decoder.is_finished());
+    assert!(decoder.rows_decoded() > 0);
+    assert_eq!(received, indices[..decoder.rows_decoded() * 64]);
+
+    decoder
+        .feed(&data[data.len() / 2..], |row| {
+            received.extend_from_slice(row.indices)
+        })
+        .unwrap();
+    assert!(decoder.is_finished());
+    assert_eq!(received, indices);
+}
+
+#[test]
+fn huge_declared_images_only_report_the_rows_received() {
+    let data = TableBasedImageData::compress(&[1; 2 * u16::MAX as usize], 2).unwrap();
+    let image = ImageDescriptor::new(0, 0, u16::MAX, u16::MAX, data);
+    let mut rows = Vec::new();
+    let result = image.decode_rows(|row| rows.push(row.y));
+    assert!(matches!(
+        result,
+        Err(GifError::ImageDataTooShort { decoded, .. }) if decoded == 2 * u16::MAX as usize
+    ));
+    assert_eq!(rows, [0, 1]);
+}
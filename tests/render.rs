@@ -0,0 +1,95 @@
+use giffer::{
+    decoder::{self, DecodeOptions, Limit},
+    render::MAX_CANVAS_PIXELS,
+    Block, GifData, GifError, GraphicRenderingBlock, ImageDescriptor, LogicalScreenDescriptor,
+    TableBasedImageData, Version,
+};
+
+const B: [u8; 4] = [0, 0, 0, 0xff];
+const W: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const R: [u8; 4] = [0xff, 0, 0, 0xff];
+const U: [u8; 4] = [0, 0, 0xff, 0xff];
+
+#[test]
+fn frames_honour_disposal_methods_and_transparency() {
+    let bytes = include_bytes!("fixtures/disposal.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let frames: Vec<_> = gif.frames().collect::<Result<_, _>>().unwrap();
+    let expected = [
+        // Do not dispose.
+        [W, W, W, W, W, W, W, W, W, W, W, W, W, W, W, W],
+        // Restore to background.
+        [R, R, W, W, R, R, W, W, W, W, W, W, W, W, W, W],
+        // Transparent pixels keep what's below, then restore to previous.
+        [B, B, W, W, B, B, W, W, W, W, U, W, W, W, W, U],
+        [B, B, W, U, B, B, W, W, W, W, W, W, W, W, W, W],
+    ];
+    assert_eq!(frames.len(), expected.len());
+    for (frame, expected) in frames.iter().zip(&expected) {
+        assert_eq!((frame.width, frame.height), (4, 4));
+        assert_eq!(frame.pixels, expected.concat());
+    }
+    let delays: Vec<_> = frames.iter().map(|frame| frame.delay_time).collect();
+    assert_eq!(delays, [10, 20, 30, 40]);
+}
+
+#[test]
+fn standalone_graphic_control_extensions_apply_to_the_next_image() {
+    let bytes = include_bytes!("fixtures/plain_text.gif");
+    let gif = decoder::decode(bytes, &DecodeOptions::new()).unwrap();
+    let delays: Vec<_> = gif
+        .frames()
+        .map(|frame| frame.unwrap().delay_time)
+        .collect();
+    assert_eq!(delays, [30, 40]);
+}
+
+#[test]
+fn huge_logical_screens_are_rejected_before_allocating_the_canvas() {
+    let image = ImageDescriptor::new(
+        0,
+        0,
+        2,
+        2,
+        TableBasedImageData::compress(&[0; 4], 2).unwrap(),
+    );
+    let gif = GifData {
+        version: Version::V89a,
+        logical_screen_descriptor: LogicalScreenDescriptor::new(u16::MAX, u16::MAX),
+        blocks: vec![Block::GraphicRendering(GraphicRenderingBlock::Image(image))],
+        trailer: None,
+    };
+    let mut frames = gif.frames();
+    match frames.next() {
+        Some(Err(GifError::LimitExceeded {
+            offset: 6,
+            limit: Limit::CanvasPixels,
+            value,
+            max: MAX_CANVAS_PIXELS,
+        })) => assert_eq!(value, u16::MAX as u64 * u16::MAX as u64),
+        frame => panic!("unexpected {:?}", frame.map(|frame| frame.map(|_| ()))),
+    }
+    assert!(frames.next().is_none());
+}
+
+#[test]
+fn the_canvas_limit_comes_from_the_decode_options() {
+    let gif = decoder::decode(
+        include_bytes!("fixtures/plain_text.gif"),
+        &DecodeOptions::new(),
+    )
+    .unwrap();
+    let mut frames = gif.frames_with_options(&DecodeOptions::new().max_canvas_pixels(63));
+    match frames.next() {
+        Some(Err(GifError::LimitExceeded {
+            offset: 6,
+            limit: Limit::CanvasPixels,
+            value: 64,
+            max: 63,
+        })) => {}
+        frame => panic!("unexpected {:?}", frame.map(|frame| frame.map(|_| ()))),
+    }
+
+    let frames = gif.frames_with_options(&DecodeOptions::new().max_canvas_pixels(64));
+    assert_eq!(frames.count(), gif.frames().count());
+}
@@ -0,0 +1,189 @@
+use giffer::{
+    decoder::{self, DecodeOptions},
+    validate::{self, Diagnostic, Problem, Severity},
+    Block, ColorTable, CommentExtension, DataSubBlocks, DisposalMethod, GifData,
+    GraphicControlExtension, GraphicRenderingBlock, ImageDescriptor, LogicalScreenDescriptor,
+    TableBasedImageData, Version,
+};
+use std::time::Duration;
+
+fn problems(diagnostics: &[Diagnostic]) -> Vec<&Problem> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| &diagnostic.problem)
+        .collect()
+}
+
+#[test]
+fn fixtures_are_valid() {
+    for bytes in [
+        &include_bytes!("fixtures/320x240.gif")[..],
+        &include_bytes!("fixtures/disposal.gif")[..],
+        &include_bytes!("fixtures/interleaved.gif")[..],
+        // The graphic control extension of the second image is kept as a block of its own.
+        &include_bytes!("fixtures/plain_text.gif")[..],
+        &include_bytes!("fixtures/unknown_extension.gif")[..],
+    ] {
+        assert_eq!(validate::validate_bytes(bytes).unwrap(), []);
+    }
+}
+
+#[test]
+fn problems_are_reported_with_their_offset() {
+    let bytes = include_bytes!("fixtures/interleaved.gif");
+    let mut damaged = bytes.to_vec();
+    // The background color index, then data after the trailer.
+    damaged[11] = 0xff;
+    damaged.extend_from_slice(b"junk");
+    let diagnostics = validate::validate_bytes(&damaged).unwrap();
+    let colors = decoder::decode(bytes, &DecodeOptions::new())
+        .unwrap()
+        .logical_screen_descriptor
+        .global_color_table
+        .unwrap()
+        .len();
+    assert_eq!(
+        diagnostics,
+        [
+            Diagnostic {
+                offset: Some(6),
+                problem: Problem::BackgroundColorOutOfRange {
+                    index: 0xff,
+                    colors
+                },
+            },
+            Diagnostic {
+                offset: Some(bytes.len()),
+                problem: Problem::TrailingData { len: 4 },
+            },
+        ]
+    );
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.severity() == Severity::Warning));
+}
+
+#[test]
+fn edited_documents_are_validated() {
+    let mut lsd = LogicalScreenDescriptor::new(16, 16);
+    // Set without updating the packed fields.
+    lsd.global_color_table = ColorTable::new(vec![0; 3 * 4]);
+    let image = |left: u16, gce: Option<GraphicControlExtension>| {
+        let mut image = ImageDescriptor::new(
+            left,
+            0,
+            8,
+            8,
+            TableBasedImageData::compress(&[0; 64], 2).unwrap(),
+        );
+        image.graphic_control_extension = gce;
+        image
+    };
+    let mut short = image(0, None);
+    short.image_data.image_data = DataSubBlocks::from_data(&[0; 600]);
+    short.image_data.image_data.blocks.swap(0, 2);
+    let gif = GifData {
+        version: Version::V87a,
+        logical_screen_descriptor: lsd,
+        blocks: vec![
+            Block::Comment(CommentExtension {
+                data: DataSubBlocks::from_data(b"comment"),
+                span: None,
+            }),
+            Block::GraphicRendering(GraphicRenderingBlock::Image(image(
+                12,
+                Some(GraphicControlExtension::new(
+                    DisposalMethod::Reserved(5),
+                    Duration::ZERO,
+                    Some(4),
+                )),
+            ))),
+            Block::GraphicRendering(GraphicRenderingBlock::Image(short)),
+        ],
+        trailer: None,
+    };
+
+    let diagnostics = validate::validate(&gif);
+    assert!(diagnostics
+        .iter()
+        .all(|diagnostic| diagnostic.offset.is_none()));
+    assert_eq!(
+        problems(&diagnostics),
+        [
+            &Problem::ColorTableMismatch {
+                global: true,
+                declared: None,
+                actual: Some(4),
+            },
+            &Problem::ExtensionIn87a {
+                extension: "comment"
+            },
+            &Problem::ImageOutsideLogicalScreen {
+                right: 20,
+                bottom: 8,
+                logical_screen_width: 16,
+                logical_screen_height: 16,
+            },
+            &Problem::ExtensionIn87a {
+                extension: "graphic control"
+            },
+            &Problem::ReservedDisposalMethod { value: 5 },
+            &Problem::TransparentColorOutOfRange {
+                index: 4,
+                colors: 4
+            },
+            &Problem::ZeroDelay,
+            &Problem::ZeroDelay,
+            &Problem::ShortSubBlocks { count: 1 },
+        ]
+    );
+    assert_eq!(
+        diagnostics[0].to_string(),
+        "error: global color table mismatch: none declared by the packed fields, 4 colors found"
+    );
+}
+
+#[test]
+fn standalone_graphic_control_extensions_apply_to_the_next_image() {
+    let mut lsd = LogicalScreenDescriptor::new(8, 8);
+    lsd.set_global_color_table(ColorTable::new(vec![0; 3 * 2]));
+    let image = || {
+        Block::GraphicRendering(GraphicRenderingBlock::Image(ImageDescriptor::new(
+            0,
+            0,
+            8,
+            8,
+            TableBasedImageData::compress(&[0; 64], 2).unwrap(),
+        )))
+    };
+    let gce = |delay: Duration| {
+        Block::GraphicControl(GraphicControlExtension::new(
+            DisposalMethod::None,
+            delay,
+            Some(2),
+        ))
+    };
+    let gif = GifData {
+        version: Version::V89a,
+        logical_screen_descriptor: lsd,
+        blocks: vec![
+            gce(Duration::from_millis(100)),
+            image(),
+            gce(Duration::ZERO),
+            Block::Comment(CommentExtension {
+                data: DataSubBlocks::from_data(b"between"),
+                span: None,
+            }),
+            image(),
+        ],
+        trailer: None,
+    };
+    let transparent = Problem::TransparentColorOutOfRange {
+        index: 2,
+        colors: 2,
+    };
+    assert_eq!(
+        problems(&validate::validate(&gif)),
+        [&transparent, &transparent, &Problem::ZeroDelay]
+    );
+}